@@ -78,32 +78,38 @@ fn main() {
     println!("cargo:rustc-link-lib=dylib=elsdc");
     println!("cargo:rustc-link-arg=-Wl,-rpath,{}", current_dir.display());
 
-    // 确认 OpenCV 库
-    let opencv = if cfg!(target_os = "macos") {
-        pkg_config::Config::new().probe("opencv4").unwrap()
+    // 确认 OpenCV 库（仅在启用 opencv feature 时）
+    let mut include_paths = Vec::new();
+    if env::var_os("CARGO_FEATURE_OPENCV").is_some() {
+        let opencv = if cfg!(target_os = "macos") {
+            pkg_config::Config::new().probe("opencv4").unwrap()
+        } else {
+            pkg_config::Config::new().probe("opencv4").unwrap_or_else(|_| pkg_config::Config::new().probe("opencv").unwrap())
+        };
+
+        log!("OpenCV libraries and paths:");
+        for path in &opencv.link_paths {
+            println!("cargo:rustc-link-search=native={}", path.display());
+            log!("Link search path: {}", path.display());
+        }
+        for lib in &opencv.libs {
+            println!("cargo:rustc-link-lib={}", lib);
+            log!("Link library: {}", lib);
+        }
+        for framework in &opencv.frameworks {
+            println!("cargo:rustc-link-lib=framework={}", framework);
+            log!("Link framework: {}", framework);
+        }
+        include_paths = opencv.include_paths;
     } else {
-        pkg_config::Config::new().probe("opencv4").unwrap_or_else(|_| pkg_config::Config::new().probe("opencv").unwrap())
-    };
-
-    log!("OpenCV libraries and paths:");
-    for path in &opencv.link_paths {
-        println!("cargo:rustc-link-search=native={}", path.display());
-        log!("Link search path: {}", path.display());
-    }
-    for lib in &opencv.libs {
-        println!("cargo:rustc-link-lib={}", lib);
-        log!("Link library: {}", lib);
-    }
-    for framework in &opencv.frameworks {
-        println!("cargo:rustc-link-lib=framework={}", framework);
-        log!("Link framework: {}", framework);
+        log!("opencv feature disabled, skipping OpenCV detection");
     }
 
     // 生成绑定代码
     let bindings = bindgen::Builder::default()
         .header("ELSDc_c/src/elsdc.h")
         .clang_arg("-IELSDc_c/src")
-        .clang_args(opencv.include_paths.iter().map(|path| format!("-I{}", path.display())))
+        .clang_args(include_paths.iter().map(|path| format!("-I{}", path.display())))
         .generate()
         .expect("Failed to generate bindings");
 
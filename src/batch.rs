@@ -0,0 +1,314 @@
+/**
+ * File: /src/batch.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 2:14:52 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::any::Any;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ElsdcError;
+
+/// Extensions picked up when a directory is given instead of a pattern.
+pub const IMAGE_EXTENSIONS: &[&str] = &["pgm", "pbm", "ppm", "pnm", "pam", "png", "jpg", "jpeg", "bmp", "tif", "tiff"];
+
+/// One image of a batch run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchInput {
+    pub path: PathBuf,
+    /// Path below the searched directory, mirrored in the output directory.
+    pub relative: PathBuf,
+}
+
+impl BatchInput {
+    /// Mirrored location of this image below `dir`, with `extension` appended
+    /// to the file name so that `a.png` and `a.pgm` give `a.png.json` and
+    /// `a.pgm.json` rather than the same file.
+    pub fn output_path<P: AsRef<Path>>(&self, dir: P, extension: &str) -> PathBuf {
+        let mut name = self.relative.clone().into_os_string();
+        name.push(".");
+        name.push(extension);
+        dir.as_ref().join(name)
+    }
+}
+
+/// Images named by `input`: every image below a directory (recursively), or
+/// the files matching a glob pattern. `*` and `?` match within one path
+/// component and `**` matches any number of directories. Results are sorted.
+pub fn find_images(input: &str) -> Result<Vec<BatchInput>, ElsdcError> {
+    let input_path = Path::new(input);
+    let (base, pattern) = if input_path.is_dir() {
+        (input_path.to_path_buf(), None)
+    } else {
+        split_pattern(input_path)
+    };
+    if !base.is_dir() {
+        return Err(ElsdcError::ConfigError(format!("No such directory: {}", base.display())));
+    }
+
+    let mut files = Vec::new();
+    walk(&base, &mut files)?;
+    let mut inputs: Vec<BatchInput> = files
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(&base).ok()?.to_path_buf();
+            let keep = match &pattern {
+                Some(pattern) => path_matches(pattern, &relative),
+                None => has_image_extension(&path),
+            };
+            keep.then_some(BatchInput { path, relative })
+        })
+        .collect();
+    inputs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(inputs)
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| IMAGE_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// Splits a pattern into the directory before its first wildcard and the
+/// remaining components.
+fn split_pattern(pattern: &Path) -> (PathBuf, Option<Vec<String>>) {
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !text.contains(['*', '?']) {
+            base.push(component);
+        } else {
+            rest.push(text.into_owned());
+        }
+    }
+    if rest.is_empty() {
+        // 没有通配符：匹配单个文件
+        let name = base.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        base.pop();
+        rest.push(name);
+    }
+    if base.as_os_str().is_empty() {
+        base.push(Component::CurDir);
+    }
+    (base, Some(rest))
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), ElsdcError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn path_matches(pattern: &[String], path: &Path) -> bool {
+    let parts: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    components_match(pattern, &parts)
+}
+
+fn components_match(pattern: &[String], parts: &[String]) -> bool {
+    match pattern.split_first() {
+        None => parts.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=parts.len()).any(|skip| components_match(rest, &parts[skip..]))
+        }
+        Some((first, rest)) => {
+            parts.split_first().is_some_and(|(part, parts)| {
+                wildcard_match(first.as_bytes(), part.as_bytes()) && components_match(rest, parts)
+            })
+        }
+    }
+}
+
+/// `*` matches any run of characters, `?` exactly one.
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (None, _) => text.is_empty(),
+        (Some((b'*', rest)), _) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        (Some((b'?', rest)), Some((_, text))) => wildcard_match(rest, text),
+        (Some((p, rest)), Some((t, text))) => p == t && wildcard_match(rest, text),
+        (Some(_), None) => false,
+    }
+}
+
+/// What a batch job reports for one image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchCounts {
+    pub rings: usize,
+    pub polygons: usize,
+}
+
+/// Outcome of one image of a batch run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BatchEntry {
+    pub input: String,
+    #[serde(flatten)]
+    pub counts: BatchCounts,
+    pub runtime_ms: f64,
+    /// Why the image failed, `None` on success.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Per-image results and totals of a batch run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub images: usize,
+    pub failed: usize,
+    pub rings: usize,
+    pub polygons: usize,
+    pub runtime_ms: f64,
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchSummary {
+    /// Writes the summary as pretty-printed JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ElsdcError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)
+            .map_err(|e| ElsdcError::IoError(e.into()))
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &BatchEntry> {
+        self.entries.iter().filter(|e| e.error.is_some())
+    }
+}
+
+/// Runs `job` on every input with `jobs` worker threads (0 for one per CPU).
+///
+/// A failing image, including one whose job panics, is recorded in the
+/// summary and does not stop the others. Entries keep the order of `inputs`.
+pub fn run_batch<F>(inputs: &[BatchInput], jobs: usize, job: F) -> Result<BatchSummary, ElsdcError>
+where
+    F: Fn(&BatchInput) -> Result<BatchCounts, ElsdcError> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .map_err(|e| ElsdcError::ConfigError(e.to_string()))?;
+    let start = Instant::now();
+    let entries: Vec<BatchEntry> = pool.install(|| {
+        inputs
+            .par_iter()
+            .map(|input| {
+                let started = Instant::now();
+                let result = panic::catch_unwind(AssertUnwindSafe(|| job(input)));
+                let runtime_ms = started.elapsed().as_secs_f64() * 1000.0;
+                let (counts, error) = match result {
+                    Ok(Ok(counts)) => (counts, None),
+                    Ok(Err(e)) => (BatchCounts::default(), Some(e.to_string())),
+                    Err(payload) => (BatchCounts::default(), Some(panic_message(payload.as_ref()))),
+                };
+                BatchEntry { input: input.path.to_string_lossy().into_owned(), counts, runtime_ms, error }
+            })
+            .collect()
+    });
+
+    Ok(BatchSummary {
+        images: entries.len(),
+        failed: entries.iter().filter(|e| e.error.is_some()).count(),
+        rings: entries.iter().map(|e| e.counts.rings).sum(),
+        polygons: entries.iter().map(|e| e.counts.polygons).sum(),
+        runtime_ms: start.elapsed().as_secs_f64() * 1000.0,
+        entries,
+    })
+}
+
+/// Message of a caught panic, for the summary.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause");
+    format!("panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(wildcard_match(b"*.png", b"a.png"));
+        assert!(wildcard_match(b"img_??.p*", b"img_01.pgm"));
+        assert!(!wildcard_match(b"*.png", b"a.pgm"));
+        assert!(!wildcard_match(b"?", b""));
+
+        let pattern: Vec<String> = ["**", "*.pgm"].iter().map(|s| s.to_string()).collect();
+        assert!(path_matches(&pattern, Path::new("a.pgm")));
+        assert!(path_matches(&pattern, Path::new("x/y/a.pgm")));
+        assert!(!path_matches(&pattern, Path::new("x/a.png")));
+    }
+
+    #[test]
+    fn test_find_images_and_mirror() {
+        let dir = std::env::temp_dir().join("elsdc_batch_find_test");
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for name in ["a.pgm", "notes.txt", "sub/b.PNG", "sub/c.pgm"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let found = find_images(&dir.to_string_lossy()).unwrap();
+        let relative: Vec<_> = found.iter().map(|i| i.relative.clone()).collect();
+        assert_eq!(relative, vec![PathBuf::from("a.pgm"), PathBuf::from("sub/b.PNG"), PathBuf::from("sub/c.pgm")]);
+        assert_eq!(found[2].output_path("out", "json"), PathBuf::from("out/sub/c.pgm.json"));
+        // 同名不同扩展名的图像不会写到同一个文件
+        let twin = BatchInput { path: dir.join("sub/c.png"), relative: PathBuf::from("sub/c.png") };
+        assert_ne!(twin.output_path("out", "json"), found[2].output_path("out", "json"));
+
+        let pattern = dir.join("**").join("*.pgm");
+        let found = find_images(&pattern.to_string_lossy()).unwrap();
+        assert_eq!(found.len(), 2);
+        let single = find_images(&dir.join("sub/c.pgm").to_string_lossy()).unwrap();
+        assert_eq!(single[0].relative, PathBuf::from("c.pgm"));
+        assert!(find_images("/nonexistent/elsdc/*.pgm").is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_run_batch_keeps_going() {
+        let inputs: Vec<BatchInput> = (0..6)
+            .map(|i| BatchInput { path: PathBuf::from(format!("{}.pgm", i)), relative: PathBuf::from(format!("{}.pgm", i)) })
+            .collect();
+        let summary = run_batch(&inputs, 3, |input| {
+            if input.path == Path::new("3.pgm") {
+                return Err(ElsdcError::ImageReadError("broken".into()));
+            }
+            if input.path == Path::new("5.pgm") {
+                panic!("decoder bug");
+            }
+            Ok(BatchCounts { rings: 2, polygons: 1 })
+        })
+        .unwrap();
+
+        assert_eq!((summary.images, summary.failed, summary.rings, summary.polygons), (6, 2, 8, 4));
+        assert_eq!(summary.entries[3].input, "3.pgm");
+        assert!(summary.entries[3].error.as_deref().unwrap().contains("broken"));
+        // 单个任务的 panic 只记为该图像的错误
+        assert_eq!(summary.entries[5].error.as_deref(), Some("panicked: decoder bug"));
+        assert_eq!(summary.failures().count(), 2);
+    }
+}
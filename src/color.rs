@@ -0,0 +1,280 @@
+/**
+ * File: /src/color.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 11:26:50 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::path::Path;
+
+#[cfg(feature = "opencv")]
+use opencv::core::{Mat, Vec3b};
+#[cfg(feature = "opencv")]
+use opencv::{imgcodecs, imgproc, prelude::*};
+
+use crate::gray_image::GrayImage;
+use crate::pgm::NetpbmImage;
+use crate::ElsdcError;
+
+/// A colour channel of an RGB image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channel {
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+}
+
+/// How a colour image is reduced to the single channel ELSDc works on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GrayConversion {
+    /// Weighted sum of the red, green and blue channels.
+    Luminance { r: f64, g: f64, b: f64 },
+    /// One channel as it is.
+    Channel(Channel),
+    /// The channel whose edges are strongest over the whole image, so edges
+    /// that only exist in chroma are not averaged away.
+    MaxGradient,
+    /// HSV value, i.e. the largest of the three channels.
+    Value,
+}
+
+impl GrayConversion {
+    /// ITU-R BT.601 weights, as used by OpenCV's `COLOR_BGR2GRAY`.
+    pub const BT601: GrayConversion = GrayConversion::Luminance { r: 0.299, g: 0.587, b: 0.114 };
+    /// ITU-R BT.709 weights.
+    pub const BT709: GrayConversion = GrayConversion::Luminance { r: 0.2126, g: 0.7152, b: 0.0722 };
+}
+
+impl Default for GrayConversion {
+    fn default() -> Self {
+        GrayConversion::BT601
+    }
+}
+
+/// RGB image with `f64` channels in `0..=255`, stored row-major.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColorImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f64; 3]>,
+}
+
+impl ColorImage {
+    /// Wraps row-major RGB pixels; `pixels` must hold `width * height` values.
+    pub fn from_rgb(width: u32, height: u32, pixels: Vec<[f64; 3]>) -> Result<Self, ElsdcError> {
+        if pixels.len() != width as usize * height as usize {
+            return Err(ElsdcError::ImageConversionError(format!(
+                "Expected {} pixels for a {}x{} image, got {}",
+                width as usize * height as usize,
+                width,
+                height,
+                pixels.len()
+            )));
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    /// Reads an image file keeping its colour channels.
+    ///
+    /// OpenCV decodes the file when available; otherwise Netpbm files are read
+    /// directly and, with the `image` feature, other formats by the `image` crate.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        let path = path.as_ref();
+        #[cfg(feature = "opencv")]
+        {
+            let mat = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_UNCHANGED)?;
+            if mat.empty() {
+                return Err(ElsdcError::ImageReadError(format!("Failed to read image: {}", path.display())));
+            }
+            Self::from_mat(&mat)
+        }
+        #[cfg(not(feature = "opencv"))]
+        {
+            #[cfg(feature = "image")]
+            if !crate::pgm::is_netpbm_path(path) {
+                return crate::image_io::open_color(path);
+            }
+            let file = std::fs::File::open(path)?;
+            let image = crate::pgm::read_netpbm(std::io::BufReader::new(file))
+                .map_err(|e| ElsdcError::ImageReadError(format!("{}: {}", path.display(), e)))?;
+            Self::try_from(&image)
+        }
+    }
+
+    /// Converts a grayscale, BGR or BGRA `Mat` (8- or 16-bit).
+    #[cfg(feature = "opencv")]
+    pub fn from_mat(mat: &Mat) -> Result<Self, ElsdcError> {
+        let mut bgr = Mat::default();
+        match mat.channels() {
+            1 => imgproc::cvt_color(mat, &mut bgr, imgproc::COLOR_GRAY2BGR, 0)?,
+            3 => bgr = mat.try_clone()?,
+            4 => imgproc::cvt_color(mat, &mut bgr, imgproc::COLOR_BGRA2BGR, 0)?,
+            n => {
+                return Err(ElsdcError::ImageConversionError(format!("Unsupported channel count: {}", n)));
+            }
+        }
+        let scale = if bgr.depth() == opencv::core::CV_16U { 1.0 / 257.0 } else { 1.0 };
+        let mut bgr8 = Mat::default();
+        bgr.convert_to(&mut bgr8, opencv::core::CV_8U, scale, 0.0)?;
+
+        let (width, height) = (bgr8.cols(), bgr8.rows());
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let p = bgr8.at_2d::<Vec3b>(y, x)?;
+                pixels.push([p[2] as f64, p[1] as f64, p[0] as f64]);
+            }
+        }
+        Self::from_rgb(width as u32, height as u32, pixels)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// RGB pixels in row-major order.
+    pub fn pixels(&self) -> &[[f64; 3]] {
+        &self.pixels
+    }
+
+    /// Reduces the image to grayscale with the given conversion.
+    pub fn to_gray(&self, conversion: GrayConversion) -> GrayImage {
+        let data = match conversion {
+            GrayConversion::Luminance { r, g, b } => {
+                self.pixels.iter().map(|p| r * p[0] + g * p[1] + b * p[2]).collect()
+            }
+            GrayConversion::Channel(c) => self.channel(c as usize),
+            GrayConversion::MaxGradient => self.channel(self.strongest_channel()),
+            GrayConversion::Value => self.pixels.iter().map(|p| p[0].max(p[1]).max(p[2])).collect(),
+        };
+        GrayImage::from_vec(self.width, self.height, data).expect("pixel count matches the image size")
+    }
+
+    fn channel(&self, c: usize) -> Vec<f64> {
+        self.pixels.iter().map(|p| p[c]).collect()
+    }
+
+    /// Channel with the largest summed gradient magnitude (forward differences).
+    fn strongest_channel(&self) -> usize {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut energy = [0.0f64; 3];
+        for y in 0..h {
+            for x in 0..w {
+                let p = self.pixels[y * w + x];
+                let right = if x + 1 < w { self.pixels[y * w + x + 1] } else { p };
+                let below = if y + 1 < h { self.pixels[(y + 1) * w + x] } else { p };
+                for c in 0..3 {
+                    energy[c] += (right[c] - p[c]).hypot(below[c] - p[c]);
+                }
+            }
+        }
+        (0..3).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap_or(0)
+    }
+}
+
+/// Colour Netpbm images keep their channels; gray ones are replicated.
+impl TryFrom<&NetpbmImage> for ColorImage {
+    type Error = ElsdcError;
+
+    fn try_from(image: &NetpbmImage) -> Result<Self, Self::Error> {
+        let scale = 255.0 / image.maxval as f64;
+        let pixels = image
+            .samples
+            .chunks_exact(image.depth)
+            .map(|px| {
+                let v = |i: usize| px[i] as f64 * scale;
+                if image.depth >= 3 {
+                    [v(0), v(1), v(2)]
+                } else {
+                    [v(0); 3]
+                }
+            })
+            .collect();
+        let width = u32::try_from(image.width)
+            .map_err(|_| ElsdcError::ImageConversionError("Image width out of range".to_string()))?;
+        let height = u32::try_from(image.height)
+            .map_err(|_| ElsdcError::ImageConversionError("Image height out of range".to_string()))?;
+        Self::from_rgb(width, height, pixels)
+    }
+}
+
+impl GrayImage {
+    /// Reads an image file in colour and reduces it with `conversion`.
+    pub fn open_with<P: AsRef<Path>>(path: P, conversion: GrayConversion) -> Result<Self, ElsdcError> {
+        Ok(ColorImage::open(path)?.to_gray(conversion))
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl crate::OpenCVImage {
+    /// Reads an image file in colour and reduces it with `conversion`.
+    pub fn open_with<P: AsRef<Path>>(path: P, conversion: GrayConversion) -> Result<Self, ElsdcError> {
+        crate::OpenCVImage::try_from(&GrayImage::open_with(path, conversion)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgm;
+    use crate::primitives::Image;
+
+    /// 灰色背景上的红色方块：亮度几乎不变，只有绿/蓝通道有边缘
+    fn red_mark() -> ColorImage {
+        let pixels = (0..64)
+            .map(|i| {
+                let (x, y) = (i % 8, i / 8);
+                if (2..6).contains(&x) && (2..6).contains(&y) {
+                    [255.0, 40.0, 40.0]
+                } else {
+                    [100.0, 100.0, 100.0]
+                }
+            })
+            .collect();
+        ColorImage::from_rgb(8, 8, pixels).unwrap()
+    }
+
+    #[test]
+    fn test_conversions() {
+        let image = red_mark();
+        let value = |conversion| image.to_gray(conversion).get_pixel(3, 3).unwrap();
+
+        assert!((value(GrayConversion::BT601) - (0.299 * 255.0 + 0.701 * 40.0)).abs() < 1e-9);
+        assert_eq!(value(GrayConversion::Channel(Channel::Blue)), 40.0);
+        assert_eq!(value(GrayConversion::Value), 255.0);
+        assert_eq!(image.to_gray(GrayConversion::Value).get_pixel(0, 0).unwrap(), 100.0);
+    }
+
+    #[test]
+    fn test_max_gradient_picks_strongest_channel() {
+        let image = red_mark();
+        // 红通道变化 155，绿/蓝通道变化 60，取红通道
+        assert_eq!(image.strongest_channel(), 0);
+        assert_eq!(image.to_gray(GrayConversion::MaxGradient).get_pixel(3, 3).unwrap(), 255.0);
+    }
+
+    #[test]
+    fn test_from_netpbm() {
+        let ppm = pgm::read_netpbm(&b"P3\n2 1\n15\n15 0 0 0 15 0\n"[..]).unwrap();
+        let image = ColorImage::try_from(&ppm).unwrap();
+        assert_eq!(image.pixels(), &[[255.0, 0.0, 0.0], [0.0, 255.0, 0.0]]);
+
+        let pgm = pgm::read_netpbm(&b"P2\n1 1\n255\n7\n"[..]).unwrap();
+        assert_eq!(ColorImage::try_from(&pgm).unwrap().pixels(), &[[7.0; 3]]);
+    }
+
+    #[test]
+    fn test_from_rgb_checks_size() {
+        assert!(ColorImage::from_rgb(2, 2, vec![[0.0; 3]; 3]).is_err());
+    }
+}
@@ -13,15 +13,18 @@
 
 use std::fmt;
 use std::error::Error;
+#[cfg(feature = "opencv")]
 use opencv::Error as OpenCVError;
 
 #[derive(Debug)]
 pub enum ElsdcError {
     IoError(std::io::Error),
+    #[cfg(feature = "opencv")]
     OpenCVError(OpenCVError),
     ImageReadError(String),
     DetectionError(String),
     ImageConversionError(String),
+    ConfigError(String),
     // 添加其他可能的错误类型
 }
 
@@ -29,10 +32,12 @@ impl fmt::Display for ElsdcError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ElsdcError::IoError(e) => write!(f, "IO error: {}", e),
+            #[cfg(feature = "opencv")]
             ElsdcError::OpenCVError(e) => write!(f, "OpenCV error: {}", e),
             ElsdcError::ImageReadError(s) => write!(f, "Image read error: {}", s),
             ElsdcError::DetectionError(s) => write!(f, "Detection error: {}", s),
             ElsdcError::ImageConversionError(s) => write!(f, "Image conversion error: {}", s),
+            ElsdcError::ConfigError(s) => write!(f, "Config error: {}", s),
         }
     }
 }
@@ -45,6 +50,7 @@ impl From<std::io::Error> for ElsdcError {
     }
 }
 
+#[cfg(feature = "opencv")]
 impl From<OpenCVError> for ElsdcError {
     fn from(error: OpenCVError) -> Self {
         ElsdcError::OpenCVError(error)
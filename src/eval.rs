@@ -0,0 +1,154 @@
+/**
+ * File: /src/eval.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 3:05:21 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use serde::{Deserialize, Serialize};
+
+use crate::ring::compatibility::margin_for;
+use crate::ring::{Ring, SimilarityMetric};
+use crate::ElsdcError;
+
+/// A detected ring paired with the ground-truth ring it was matched to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RingMatch {
+    pub detected: usize,
+    pub truth: usize,
+    pub similarity: f64,
+}
+
+/// Precision and recall of detected rings against ground truth.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub detected: usize,
+    pub truth: usize,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Mean similarity of the matched pairs.
+    pub mean_similarity: f64,
+    pub matches: Vec<RingMatch>,
+}
+
+/// Matches `detected` rings to `truth` one-to-one and scores the result.
+///
+/// Pairs are taken greedily from the most similar down; a pair counts as a
+/// true positive when its similarity reaches `threshold`. Precision and
+/// recall of an empty side are 1 when the other side is empty too.
+pub fn evaluate(
+    detected: &[Ring],
+    truth: &[Ring],
+    metric: SimilarityMetric,
+    threshold: f64,
+) -> Result<Evaluation, ElsdcError> {
+    // 与兼容性矩阵相同的剪枝余量，弧重叠在容差内的配对不会被跳过
+    let margin = margin_for(metric);
+    let mut pairs = Vec::new();
+    for (i, d) in detected.iter().enumerate() {
+        for (j, t) in truth.iter().enumerate() {
+            if !d.bounding_box().expand(margin).intersects(&t.bounding_box()) {
+                continue;
+            }
+            let similarity = d.similarity(t, metric)?;
+            if similarity >= threshold {
+                pairs.push(RingMatch { detected: i, truth: j, similarity });
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+
+    let (mut used_detected, mut used_truth) = (vec![false; detected.len()], vec![false; truth.len()]);
+    let mut matches = Vec::new();
+    for pair in pairs {
+        if !used_detected[pair.detected] && !used_truth[pair.truth] {
+            used_detected[pair.detected] = true;
+            used_truth[pair.truth] = true;
+            matches.push(pair);
+        }
+    }
+    matches.sort_by_key(|m| m.detected);
+
+    let tp = matches.len();
+    let both_empty = detected.is_empty() && truth.is_empty();
+    let ratio = |n: usize, total: usize| match total {
+        0 if both_empty => 1.0,
+        0 => 0.0,
+        _ => n as f64 / total as f64,
+    };
+    let (precision, recall) = (ratio(tp, detected.len()), ratio(tp, truth.len()));
+    let f1 = if precision + recall > 0.0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 };
+    let mean_similarity =
+        if tp == 0 { 0.0 } else { matches.iter().map(|m| m.similarity).sum::<f64>() / tp as f64 };
+
+    Ok(Evaluation {
+        detected: detected.len(),
+        truth: truth.len(),
+        true_positives: tp,
+        false_positives: detected.len() - tp,
+        false_negatives: truth.len() - tp,
+        precision,
+        recall,
+        f1,
+        mean_similarity,
+        matches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_counts() {
+        let truth = [Ring::circle(20.0, 20.0, 10.0), Ring::circle(80.0, 20.0, 10.0), Ring::circle(50.0, 70.0, 8.0)];
+        let detected = [Ring::circle(20.5, 20.0, 10.0), Ring::circle(80.0, 20.0, 10.0), Ring::circle(150.0, 150.0, 5.0)];
+        let eval = evaluate(&detected, &truth, SimilarityMetric::default(), 0.5).unwrap();
+
+        assert_eq!((eval.true_positives, eval.false_positives, eval.false_negatives), (2, 1, 1));
+        assert!((eval.precision - 2.0 / 3.0).abs() < 1e-12);
+        assert!((eval.recall - 2.0 / 3.0).abs() < 1e-12);
+        assert_eq!(eval.matches[0].truth, 0);
+        assert!(eval.mean_similarity > 0.9);
+    }
+
+    #[test]
+    fn test_evaluate_is_one_to_one() {
+        // 两个检测结果对应同一个真值，只有更相似的那个算作正确
+        let truth = [Ring::circle(20.0, 20.0, 10.0)];
+        let detected = [Ring::circle(21.0, 20.0, 10.0), Ring::circle(20.0, 20.0, 10.0)];
+        let eval = evaluate(&detected, &truth, SimilarityMetric::default(), 0.5).unwrap();
+        assert_eq!(eval.matches.len(), 1);
+        assert_eq!(eval.matches[0].detected, 1);
+        assert_eq!(eval.false_positives, 1);
+
+        let empty = evaluate(&[], &[], SimilarityMetric::default(), 0.5).unwrap();
+        assert_eq!((empty.precision, empty.recall), (1.0, 1.0));
+        let missed = evaluate(&[], &truth, SimilarityMetric::default(), 0.5).unwrap();
+        assert_eq!((missed.precision, missed.recall, missed.f1), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_evaluate_arc_overlap_across_boxes() {
+        // 包围盒相距 1.5 像素，但曲线在 2 像素容差内相邻
+        let truth = [Ring::circle(0.0, 0.0, 10.0)];
+        let detected = [Ring::circle(21.5, 0.0, 10.0)];
+        let metric = SimilarityMetric::ArcOverlap { tolerance: 2.0 };
+        let similarity = detected[0].similarity(&truth[0], metric).unwrap();
+        assert!(similarity > 0.05);
+
+        let eval = evaluate(&detected, &truth, metric, 0.05).unwrap();
+        assert_eq!(eval.true_positives, 1);
+        assert_eq!(eval.matches[0].similarity, similarity);
+    }
+}
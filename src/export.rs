@@ -0,0 +1,410 @@
+/**
+ * File: /src/export.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 8:31:52 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::elsdc::Detection;
+use crate::polygon::Polygon;
+use crate::primitives::DetectedPrimitive;
+use crate::ring::Ring;
+use crate::ElsdcError;
+
+/// Output formats of a [`DetectionReport`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single pretty-printed JSON document.
+    #[default]
+    Json,
+    /// One row per primitive, with the image metadata repeated on every row.
+    Csv,
+    /// One JSON object per line: the image first, then every primitive.
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// Conventional file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ElsdcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            _ => Err(ElsdcError::ConfigError(format!("Unknown export format: {}", s))),
+        }
+    }
+}
+
+/// Metadata of the image a report was produced from.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub path: Option<String>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A detected ring with its position in the detection and its label-map label.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RingRecord {
+    pub index: usize,
+    pub label: i32,
+    #[serde(flatten)]
+    pub ring: Ring,
+}
+
+/// A detected polygon with its position in the detection and its label-map label.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PolygonRecord {
+    pub index: usize,
+    pub label: i32,
+    #[serde(flatten)]
+    pub polygon: Polygon,
+}
+
+/// Serialisable summary of a [`Detection`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetectionReport {
+    pub image: ImageInfo,
+    pub rings: Vec<RingRecord>,
+    pub polygons: Vec<PolygonRecord>,
+}
+
+/// Tagged line of the NDJSON output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Line<'a> {
+    Image(&'a ImageInfo),
+    Ring(&'a RingRecord),
+    Polygon(&'a PolygonRecord),
+}
+
+/// Owned counterpart of [`Line`] used when reading NDJSON back.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum OwnedLine {
+    Image(ImageInfo),
+    Ring(RingRecord),
+    Polygon(PolygonRecord),
+}
+
+const CSV_HEADER: &str = "image,image_width,image_height,type,index,label,\
+cx,cy,ax,bx,theta,ang_start,ang_end,full,width,wmin,wmax,x1,y1,x2,y2,points";
+/// Number of ring-only columns, left empty on polygon rows.
+const RING_COLUMNS: usize = 15;
+
+impl DetectionReport {
+    /// Builds a report of `detection`; the image size is taken from its label map.
+    pub fn new(detection: &Detection) -> Self {
+        let image = ImageInfo {
+            path: None,
+            width: detection.labels.width,
+            height: detection.labels.height,
+        };
+        let rings = detection
+            .rings
+            .iter()
+            .zip(&detection.ring_labels)
+            .enumerate()
+            .map(|(index, (&ring, &label))| RingRecord { index, label, ring })
+            .collect();
+        let polygons = detection
+            .polygons
+            .iter()
+            .zip(&detection.polygon_labels)
+            .enumerate()
+            .map(|(index, (polygon, &label))| PolygonRecord { index, label, polygon: polygon.clone() })
+            .collect();
+        Self { image, rings, polygons }
+    }
+
+    /// Records the path of the source image.
+    pub fn with_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.image.path = Some(path.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Writes the report to `writer` in the given format.
+    pub fn write<W: Write>(&self, mut writer: W, format: ExportFormat) -> Result<(), ElsdcError> {
+        match format {
+            ExportFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self).map_err(std::io::Error::from)?;
+                writeln!(writer)?;
+            }
+            ExportFormat::Ndjson => {
+                let lines = std::iter::once(Line::Image(&self.image))
+                    .chain(self.rings.iter().map(Line::Ring))
+                    .chain(self.polygons.iter().map(Line::Polygon));
+                for line in lines {
+                    serde_json::to_writer(&mut writer, &line).map_err(std::io::Error::from)?;
+                    writeln!(writer)?;
+                }
+            }
+            ExportFormat::Csv => self.write_csv(&mut writer)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the report to `path`, creating parent directories as needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ExportFormat) -> Result<(), ElsdcError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.write(BufWriter::new(File::create(path)?), format)
+    }
+
+    /// Serialises the report to a string in the given format.
+    pub fn to_string(&self, format: ExportFormat) -> Result<String, ElsdcError> {
+        let mut buffer = Vec::new();
+        self.write(&mut buffer, format)?;
+        String::from_utf8(buffer).map_err(|e| ElsdcError::DetectionError(e.to_string()))
+    }
+
+    /// Reads a report written in JSON or NDJSON; CSV is write-only.
+    pub fn read<R: BufRead>(reader: R, format: ExportFormat) -> Result<Self, ElsdcError> {
+        let invalid = |e: serde_json::Error| ElsdcError::IoError(e.into());
+        match format {
+            ExportFormat::Json => serde_json::from_reader(reader).map_err(invalid),
+            ExportFormat::Ndjson => {
+                let mut report = DetectionReport {
+                    image: ImageInfo::default(),
+                    rings: Vec::new(),
+                    polygons: Vec::new(),
+                };
+                for line in reader.lines() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str(&line).map_err(invalid)? {
+                        OwnedLine::Image(image) => report.image = image,
+                        OwnedLine::Ring(ring) => report.rings.push(ring),
+                        OwnedLine::Polygon(polygon) => report.polygons.push(polygon),
+                    }
+                }
+                Ok(report)
+            }
+            ExportFormat::Csv => Err(ElsdcError::ConfigError("CSV reports cannot be read back".into())),
+        }
+    }
+
+    /// Reads a report from `path`, choosing the format from its extension.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        let path = path.as_ref();
+        let format = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().parse())
+            .transpose()?
+            .unwrap_or_default();
+        Self::read(BufReader::new(File::open(path)?), format)
+    }
+
+    /// The reported rings, in detection order.
+    pub fn rings(&self) -> Vec<Ring> {
+        self.rings.iter().map(|record| record.ring).collect()
+    }
+
+    /// Returns the reported rings followed by the polygons, classified by kind.
+    pub fn primitives(&self) -> Vec<DetectedPrimitive> {
+        let rings = self.rings.iter().map(|record| DetectedPrimitive::from(record.ring));
+        let polygons = self.polygons.iter().map(|record| DetectedPrimitive::from(record.polygon.clone()));
+        rings.chain(polygons).collect()
+    }
+
+    fn write_csv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "{}", CSV_HEADER)?;
+        let image = format!(
+            "{},{},{}",
+            csv_field(self.image.path.as_deref().unwrap_or("")),
+            self.image.width,
+            self.image.height
+        );
+        for record in &self.rings {
+            let r = &record.ring;
+            writeln!(
+                writer,
+                "{},ring,{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},",
+                image,
+                record.index,
+                record.label,
+                r.cx,
+                r.cy,
+                r.ax,
+                r.bx,
+                r.theta,
+                r.ang_start,
+                r.ang_end,
+                r.full,
+                r.width,
+                r.wmin,
+                r.wmax,
+                r.x1,
+                r.y1,
+                r.x2,
+                r.y2
+            )?;
+        }
+        for record in &self.polygons {
+            // 多边形顶点写成 "x y;x y;..."
+            let mut points = String::new();
+            for (i, p) in record.polygon.points.iter().enumerate() {
+                if i > 0 {
+                    points.push(';');
+                }
+                let _ = write!(points, "{} {}", p.x, p.y);
+            }
+            writeln!(
+                writer,
+                "{},polygon,{},{},{}{}",
+                image,
+                record.index,
+                record.label,
+                ",".repeat(RING_COLUMNS),
+                points
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::label_map::LabelMap;
+    use crate::polygon::PointD;
+
+    fn sample_detection() -> Detection {
+        let ring = Ring {
+            x1: 30.0,
+            y1: 20.0,
+            x2: 10.0,
+            y2: 20.0,
+            width: 2.0,
+            cx: 20.0,
+            cy: 20.0,
+            theta: 0.0,
+            ax: 10.0,
+            bx: 10.0,
+            ang_start: 0.0,
+            ang_end: std::f64::consts::PI,
+            wmin: 1.5,
+            wmax: 2.5,
+            full: 0,
+        };
+        let polygon = Polygon {
+            points: vec![PointD { x: 1.0, y: 2.0 }, PointD { x: 3.5, y: 4.0 }],
+        };
+        Detection {
+            rings: vec![ring],
+            ring_labels: vec![1],
+            polygons: vec![polygon],
+            polygon_labels: vec![2],
+            labels: LabelMap::new(40, 30, vec![0; 40 * 30]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let report = DetectionReport::new(&sample_detection()).with_path("images/a,b.png");
+        let json = report.to_string(ExportFormat::Json).unwrap();
+        let parsed: DetectionReport = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.image, report.image);
+        assert_eq!(parsed.image.width, 40);
+        assert_eq!(parsed.rings.len(), 1);
+        assert_eq!(parsed.rings[0].label, 1);
+        assert_eq!(parsed.rings[0].ring.ang_end, std::f64::consts::PI);
+        assert_eq!(parsed.rings[0].ring.wmax, 2.5);
+        assert_eq!(parsed.polygons[0].polygon, report.polygons[0].polygon);
+    }
+
+    #[test]
+    fn test_ndjson_lines() {
+        let report = DetectionReport::new(&sample_detection());
+        let text = report.to_string(ExportFormat::Ndjson).unwrap();
+        let lines: Vec<serde_json::Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "image");
+        assert_eq!(lines[0]["height"], 30);
+        assert_eq!(lines[1]["type"], "ring");
+        assert_eq!(lines[1]["cx"], 20.0);
+        assert_eq!(lines[1]["full"], 0);
+        assert_eq!(lines[2]["type"], "polygon");
+        assert_eq!(lines[2]["points"][1]["x"], 3.5);
+    }
+
+    #[test]
+    fn test_read_back() {
+        let report = DetectionReport::new(&sample_detection()).with_path("a.pgm");
+        for format in [ExportFormat::Json, ExportFormat::Ndjson] {
+            let text = report.to_string(format).unwrap();
+            let parsed = DetectionReport::read(text.as_bytes(), format).unwrap();
+            assert_eq!(parsed.image, report.image);
+            assert_eq!(parsed.rings().len(), 1);
+            assert_eq!(parsed.rings()[0].cx, 20.0);
+            assert_eq!(parsed.polygons[0].polygon, report.polygons[0].polygon);
+            assert_eq!(parsed.primitives().len(), 2);
+        }
+        assert!(DetectionReport::read("".as_bytes(), ExportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_csv_rows() {
+        let report = DetectionReport::new(&sample_detection()).with_path("images/a,b.png");
+        let text = report.to_string(ExportFormat::Csv).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        let columns = CSV_HEADER.split(',').count();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("\"images/a,b.png\",40,30,ring,0,1,20,20,10,10,0,0,"));
+        assert!(lines[2].ends_with(",1 2;3.5 4"));
+        // 引号内的逗号不计入列数
+        for line in &lines[1..] {
+            assert_eq!(line.replace("\"images/a,b.png\"", "x").split(',').count(), columns);
+        }
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("JSON".parse::<ExportFormat>().unwrap(), ExportFormat::Json);
+        assert_eq!("jsonl".parse::<ExportFormat>().unwrap(), ExportFormat::Ndjson);
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,145 @@
+/**
+ * File: /src/geometry.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 3:21:48 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+pub(crate) type Pt = (f64, f64);
+
+/// Z component of `(a - o) x (b - o)`; positive when `b` is left of `o -> a`.
+pub(crate) fn cross(o: Pt, a: Pt, b: Pt) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Signed area of a simple polygon, positive for counter-clockwise order.
+pub(crate) fn signed_area(poly: &[Pt]) -> f64 {
+    if poly.len() < 3 {
+        return 0.0;
+    }
+    let mut sum = 0.0;
+    for i in 0..poly.len() {
+        let (p, q) = (poly[i], poly[(i + 1) % poly.len()]);
+        sum += p.0 * q.1 - q.0 * p.1;
+    }
+    sum / 2.0
+}
+
+/// Reorders `poly` counter-clockwise.
+pub(crate) fn make_ccw(poly: &mut [Pt]) {
+    if signed_area(poly) < 0.0 {
+        poly.reverse();
+    }
+}
+
+/// Sutherland–Hodgman clipping of `subject` by the convex, counter-clockwise `clip`.
+pub(crate) fn clip_convex(subject: &[Pt], clip: &[Pt]) -> Vec<Pt> {
+    let mut output = subject.to_vec();
+    for i in 0..clip.len() {
+        if output.is_empty() {
+            break;
+        }
+        let (a, b) = (clip[i], clip[(i + 1) % clip.len()]);
+        let input = std::mem::take(&mut output);
+        let mut prev = input[input.len() - 1];
+        let mut prev_side = cross(a, b, prev);
+        for &cur in &input {
+            let cur_side = cross(a, b, cur);
+            if cur_side >= 0.0 {
+                if prev_side < 0.0 {
+                    output.push(intersect(prev, cur, prev_side, cur_side));
+                }
+                output.push(cur);
+            } else if prev_side >= 0.0 {
+                output.push(intersect(prev, cur, prev_side, cur_side));
+            }
+            prev = cur;
+            prev_side = cur_side;
+        }
+    }
+    output
+}
+
+/// Point where segment `p -> q` crosses the clip line, given their signed sides.
+fn intersect(p: Pt, q: Pt, side_p: f64, side_q: f64) -> Pt {
+    let t = side_p / (side_p - side_q);
+    (p.0 + t * (q.0 - p.0), p.1 + t * (q.1 - p.1))
+}
+
+/// Euclidean distance from `p` to the segment `a -> b`.
+pub(crate) fn point_segment_distance(p: Pt, a: Pt, b: Pt) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (qx, qy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - qx).powi(2) + (p.1 - qy).powi(2)).sqrt()
+}
+
+/// Distance from `p` to the open polyline `line`.
+pub(crate) fn polyline_distance(p: Pt, line: &[Pt]) -> f64 {
+    match line {
+        [] => f64::INFINITY,
+        [q] => ((p.0 - q.0).powi(2) + (p.1 - q.1).powi(2)).sqrt(),
+        _ => line
+            .windows(2)
+            .map(|w| point_segment_distance(p, w[0], w[1]))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+/// Whether `p` lies inside (or on) the convex, counter-clockwise polygon `poly`.
+#[cfg_attr(feature = "opencv", allow(dead_code))]
+pub(crate) fn contains_convex(poly: &[Pt], p: Pt) -> bool {
+    poly.len() >= 3 && (0..poly.len()).all(|i| cross(poly[i], poly[(i + 1) % poly.len()], p) >= 0.0)
+}
+
+/// Area shared by two convex, counter-clockwise polygons.
+pub(crate) fn convex_intersection_area(a: &[Pt], b: &[Pt]) -> f64 {
+    signed_area(&clip_convex(a, b)).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, side: f64) -> Vec<Pt> {
+        vec![(x, y), (x + side, y), (x + side, y + side), (x, y + side)]
+    }
+
+    #[test]
+    fn test_signed_area_orientation() {
+        let mut sq = square(0.0, 0.0, 2.0);
+        assert_eq!(signed_area(&sq), 4.0);
+        sq.reverse();
+        assert_eq!(signed_area(&sq), -4.0);
+        make_ccw(&mut sq);
+        assert_eq!(signed_area(&sq), 4.0);
+    }
+
+    #[test]
+    fn test_convex_intersection_area() {
+        let a = square(0.0, 0.0, 2.0);
+        assert_eq!(convex_intersection_area(&a, &square(1.0, 1.0, 2.0)), 1.0);
+        assert_eq!(convex_intersection_area(&a, &square(3.0, 3.0, 1.0)), 0.0);
+        assert_eq!(convex_intersection_area(&a, &a), 4.0);
+    }
+
+    #[test]
+    fn test_polyline_distance() {
+        let line = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0)];
+        assert_eq!(polyline_distance((1.0, 1.0), &line), 1.0);
+        assert_eq!(polyline_distance((3.0, 3.0), &line), 2.0_f64.sqrt());
+        assert_eq!(polyline_distance((1.0, 0.0), &line), 0.0);
+        assert_eq!(polyline_distance((1.0, 0.0), &[]), f64::INFINITY);
+    }
+}
@@ -0,0 +1,265 @@
+/**
+ * File: /src/gray_image.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 9:18:03 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::any::Any;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+use libc::c_double;
+
+use crate::geometry::{self, Pt};
+use crate::pgm::{self, PImageDouble};
+use crate::primitives::Image;
+use crate::ElsdcError;
+
+/// Grayscale image stored as row-major `f64` values, usually in `0..=255`.
+///
+/// This is the OpenCV-free counterpart of `OpenCVImage`: it can be handed to
+/// the detector directly and primitives can be drawn on it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GrayImage {
+    width: u32,
+    height: u32,
+    data: Vec<f64>,
+}
+
+impl GrayImage {
+    /// Black image of the given size.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, data: vec![0.0; width as usize * height as usize] }
+    }
+
+    /// Wraps row-major pixel values; `data` must hold `width * height` values.
+    pub fn from_vec(width: u32, height: u32, data: Vec<f64>) -> Result<Self, ElsdcError> {
+        if data.len() != width as usize * height as usize {
+            return Err(ElsdcError::ImageConversionError(format!(
+                "Expected {} pixels for a {}x{} image, got {}",
+                width as usize * height as usize,
+                width,
+                height,
+                data.len()
+            )));
+        }
+        Ok(Self { width, height, data })
+    }
+
+    /// Reads an image file.
+    ///
+    /// Netpbm files (PBM, PGM, PPM, PAM) are read directly. With the `image`
+    /// feature any other format it decodes (PNG, JPEG, BMP, TIFF, ...) is
+    /// converted to grayscale.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        let path = path.as_ref();
+        #[cfg(feature = "image")]
+        if !pgm::is_netpbm_path(path) {
+            return crate::image_io::open(path);
+        }
+        let image = pgm::read_pgm_image_double_rust(&path.to_string_lossy())
+            .map_err(|e| ElsdcError::ImageReadError(format!("{}: {}", path.display(), e)))?;
+        Self::try_from(image)
+    }
+
+    /// Writes the image as an 8-bit binary PGM, clamping values to `0..=255`.
+    ///
+    /// With the `image` feature, paths without a Netpbm extension are encoded
+    /// in the format given by their extension instead.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ElsdcError> {
+        let path = path.as_ref();
+        #[cfg(feature = "image")]
+        if !pgm::is_netpbm_path(path) {
+            return crate::image_io::save(self, path);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        pgm::write_gray(
+            &mut writer,
+            &self.data,
+            self.width as usize,
+            self.height as usize,
+            255,
+            pgm::Encoding::Raw,
+        )?;
+        Ok(())
+    }
+
+    /// Pixel values in row-major order.
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// Mutable pixel values in row-major order.
+    pub fn data_mut(&mut self) -> &mut [f64] {
+        &mut self.data
+    }
+
+    pub fn into_vec(self) -> Vec<f64> {
+        self.data
+    }
+
+    /// Draws the polyline through `points` with the given value and thickness.
+    ///
+    /// Every pixel whose centre lies within `thickness / 2` (at least half a
+    /// pixel) of the polyline is set, which keeps thin lines connected.
+    pub fn draw_polyline(&mut self, points: &[(f64, f64)], value: f64, thickness: f64) {
+        let radius = (thickness / 2.0).max(0.5);
+        for segment in points.windows(2) {
+            self.draw_segment(segment[0], segment[1], value, radius);
+        }
+        if let [p] = points {
+            self.draw_segment(*p, *p, value, radius);
+        }
+    }
+
+    fn draw_segment(&mut self, a: Pt, b: Pt, value: f64, radius: f64) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let clamp_x = |v: f64| v.clamp(0.0, (self.width - 1) as f64) as u32;
+        let clamp_y = |v: f64| v.clamp(0.0, (self.height - 1) as f64) as u32;
+        let (x0, x1) = (clamp_x((a.0.min(b.0) - radius).floor()), clamp_x((a.0.max(b.0) + radius).ceil()));
+        let (y0, y1) = (clamp_y((a.1.min(b.1) - radius).floor()), clamp_y((a.1.max(b.1) + radius).ceil()));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if geometry::point_segment_distance((x as f64, y as f64), a, b) <= radius {
+                    self.data[y as usize * self.width as usize + x as usize] = value;
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> Result<usize, Box<dyn std::error::Error>> {
+        if x >= self.width || y >= self.height {
+            return Err(Box::new(ElsdcError::ImageConversionError(format!(
+                "Pixel ({}, {}) is outside the {}x{} image",
+                x, y, self.width, self.height
+            ))));
+        }
+        Ok(y as usize * self.width as usize + x as usize)
+    }
+}
+
+impl TryFrom<PImageDouble> for GrayImage {
+    type Error = ElsdcError;
+
+    fn try_from(image: PImageDouble) -> Result<Self, Self::Error> {
+        let width = u32::try_from(image.xsize)
+            .map_err(|_| ElsdcError::ImageConversionError("Image width out of range".to_string()))?;
+        let height = u32::try_from(image.ysize)
+            .map_err(|_| ElsdcError::ImageConversionError("Image height out of range".to_string()))?;
+        Self::from_vec(width, height, image.data)
+    }
+}
+
+impl TryFrom<&str> for GrayImage {
+    type Error = ElsdcError;
+
+    fn try_from(path: &str) -> Result<Self, Self::Error> {
+        Self::open(path)
+    }
+}
+
+impl TryFrom<&String> for GrayImage {
+    type Error = ElsdcError;
+
+    fn try_from(path: &String) -> Result<Self, Self::Error> {
+        Self::open(path)
+    }
+}
+
+// `data` always holds `width * height` pixels (checked by every constructor).
+unsafe impl Image for GrayImage {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, value: f64) -> Result<(), Box<dyn std::error::Error>> {
+        let i = self.index(x, y)?;
+        self.data[i] = value;
+        Ok(())
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Result<f64, Box<dyn std::error::Error>> {
+        Ok(self.data[self.index(x, y)?])
+    }
+
+    fn as_ptr(&self) -> *const c_double {
+        self.data.as_ptr()
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut f64 {
+        self.data.as_mut_ptr()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_vec_checks_size() {
+        assert!(GrayImage::from_vec(3, 2, vec![0.0; 6]).is_ok());
+        assert!(GrayImage::from_vec(3, 2, vec![0.0; 5]).is_err());
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut image = GrayImage::new(4, 3);
+        image.set_pixel(3, 2, 7.0).unwrap();
+        assert_eq!(image.get_pixel(3, 2).unwrap(), 7.0);
+        assert_eq!(image.data()[11], 7.0);
+        assert!(image.get_pixel(4, 0).is_err());
+    }
+
+    #[test]
+    fn test_draw_polyline() {
+        let mut image = GrayImage::from_vec(10, 10, vec![255.0; 100]).unwrap();
+        image.draw_polyline(&[(1.0, 1.0), (8.0, 1.0), (8.0, 8.0)], 0.0, 1.0);
+
+        for x in 1..=8 {
+            assert_eq!(image.get_pixel(x, 1).unwrap(), 0.0);
+        }
+        for y in 1..=8 {
+            assert_eq!(image.get_pixel(8, y).unwrap(), 0.0);
+        }
+        assert_eq!(image.get_pixel(1, 5).unwrap(), 255.0);
+        assert_eq!(image.data().iter().filter(|&&v| v == 0.0).count(), 15);
+    }
+
+    #[test]
+    fn test_save_and_open_round_trip() {
+        let dir = std::env::temp_dir().join("elsdc_gray_image_test");
+        let path = dir.join("round_trip.pgm");
+        let data: Vec<f64> = (0..12).map(|v| (v * 20) as f64).collect();
+        let image = GrayImage::from_vec(4, 3, data).unwrap();
+
+        image.save(&path).unwrap();
+        let loaded = GrayImage::open(&path).unwrap();
+        assert_eq!(loaded, image);
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,218 @@
+/**
+ * File: /src/image_io.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 10:02:37 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::fs;
+use std::path::Path;
+
+use image::{DynamicImage, ImageBuffer, Luma};
+
+use crate::color::ColorImage;
+use crate::gray_image::GrayImage;
+use crate::primitives::Image;
+use crate::ElsdcError;
+
+/// 16-bit grayscale buffer of the `image` crate.
+pub type Gray16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// Decodes any format supported by the `image` crate into a [`GrayImage`].
+pub(crate) fn open(path: &Path) -> Result<GrayImage, ElsdcError> {
+    let decoded = image::open(path)
+        .map_err(|e| ElsdcError::ImageReadError(format!("{}: {}", path.display(), e)))?;
+    Ok(GrayImage::from(&decoded))
+}
+
+/// Decodes any format supported by the `image` crate keeping its colour channels.
+#[cfg_attr(feature = "opencv", allow(dead_code))]
+pub(crate) fn open_color(path: &Path) -> Result<ColorImage, ElsdcError> {
+    let decoded = image::open(path)
+        .map_err(|e| ElsdcError::ImageReadError(format!("{}: {}", path.display(), e)))?;
+    Ok(ColorImage::from(&decoded))
+}
+
+/// Encodes `gray` in the format given by the extension of `path`.
+pub(crate) fn save(gray: &GrayImage, path: &Path) -> Result<(), ElsdcError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    image::GrayImage::from(gray)
+        .save(path)
+        .map_err(|e| ElsdcError::ImageConversionError(format!("{}: {}", path.display(), e)))
+}
+
+/// Encodes interleaved RGB bytes in the format given by the extension of `path`.
+#[cfg(not(feature = "opencv"))]
+pub(crate) fn save_rgb(rgb: Vec<u8>, width: u32, height: u32, path: &Path) -> Result<(), ElsdcError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let buffer = image::RgbImage::from_raw(width, height, rgb)
+        .ok_or_else(|| ElsdcError::ImageConversionError("RGB buffer does not match its dimensions".into()))?;
+    buffer
+        .save(path)
+        .map_err(|e| ElsdcError::ImageConversionError(format!("{}: {}", path.display(), e)))
+}
+
+/// Encodes `gray` as an in-memory PNG.
+pub(crate) fn encode_png(gray: &GrayImage) -> Result<Vec<u8>, ElsdcError> {
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image::GrayImage::from(gray)
+        .write_to(&mut buffer, image::ImageFormat::Png)
+        .map_err(|e| ElsdcError::ImageConversionError(e.to_string()))?;
+    Ok(buffer.into_inner())
+}
+
+impl From<&image::GrayImage> for GrayImage {
+    fn from(buffer: &image::GrayImage) -> Self {
+        let data = buffer.as_raw().iter().map(|&v| v as f64).collect();
+        GrayImage::from_vec(buffer.width(), buffer.height(), data).expect("buffer size matches its dimensions")
+    }
+}
+
+/// 16-bit values are scaled to `0..=255` (divided by 257), the range ELSDc expects.
+impl From<&Gray16Image> for GrayImage {
+    fn from(buffer: &Gray16Image) -> Self {
+        let data = buffer.as_raw().iter().map(|&v| v as f64 / 257.0).collect();
+        GrayImage::from_vec(buffer.width(), buffer.height(), data).expect("buffer size matches its dimensions")
+    }
+}
+
+/// Colour images are reduced to luminance; 16-bit and float images keep their precision.
+impl From<&DynamicImage> for GrayImage {
+    fn from(image: &DynamicImage) -> Self {
+        match image {
+            DynamicImage::ImageLuma8(buffer) => GrayImage::from(buffer),
+            DynamicImage::ImageLuma16(buffer) => GrayImage::from(buffer),
+            DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_) => {
+                GrayImage::from(&image.to_luma16())
+            }
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                let buffer = image.to_luma32f();
+                let data = buffer.as_raw().iter().map(|&v| v as f64 * 255.0).collect();
+                GrayImage::from_vec(buffer.width(), buffer.height(), data)
+                    .expect("buffer size matches its dimensions")
+            }
+            _ => GrayImage::from(&image.to_luma8()),
+        }
+    }
+}
+
+impl From<DynamicImage> for GrayImage {
+    fn from(image: DynamicImage) -> Self {
+        GrayImage::from(&image)
+    }
+}
+
+/// Channels are scaled to `0..=255` whatever the bit depth; alpha is dropped.
+impl From<&DynamicImage> for ColorImage {
+    fn from(image: &DynamicImage) -> Self {
+        let buffer = image.to_rgb32f();
+        let pixels = buffer
+            .pixels()
+            .map(|p| p.0.map(|v| v as f64 * 255.0))
+            .collect();
+        ColorImage::from_rgb(buffer.width(), buffer.height(), pixels).expect("buffer size matches its dimensions")
+    }
+}
+
+/// Values are rounded and clamped to `0..=255`.
+impl From<&GrayImage> for image::GrayImage {
+    fn from(gray: &GrayImage) -> Self {
+        let data = gray.data().iter().map(|v| v.round().clamp(0.0, 255.0) as u8).collect();
+        image::GrayImage::from_raw(gray.width(), gray.height(), data).expect("buffer size matches its dimensions")
+    }
+}
+
+/// Values in `0..=255` are spread over the full 16-bit range (multiplied by 257).
+impl From<&GrayImage> for Gray16Image {
+    fn from(gray: &GrayImage) -> Self {
+        let data = gray
+            .data()
+            .iter()
+            .map(|v| (v * 257.0).round().clamp(0.0, u16::MAX as f64) as u16)
+            .collect();
+        Gray16Image::from_raw(gray.width(), gray.height(), data).expect("buffer size matches its dimensions")
+    }
+}
+
+impl From<&GrayImage> for DynamicImage {
+    fn from(gray: &GrayImage) -> Self {
+        DynamicImage::ImageLuma8(image::GrayImage::from(gray))
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl TryFrom<&DynamicImage> for crate::OpenCVImage {
+    type Error = ElsdcError;
+
+    fn try_from(image: &DynamicImage) -> Result<Self, Self::Error> {
+        crate::OpenCVImage::try_from(&GrayImage::from(image))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_luma8_round_trip() {
+        let buffer = image::GrayImage::from_fn(5, 3, |x, y| Luma([(x * 40 + y) as u8]));
+        let gray = GrayImage::from(&buffer);
+        assert_eq!(gray.get_pixel(4, 2).unwrap(), 162.0);
+        assert_eq!(image::GrayImage::from(&gray), buffer);
+    }
+
+    #[test]
+    fn test_luma16_scaling() {
+        let buffer = Gray16Image::from_fn(2, 1, |x, _| Luma([if x == 0 { 0 } else { u16::MAX }]));
+        let gray = GrayImage::from(&DynamicImage::ImageLuma16(buffer.clone()));
+        assert_eq!(gray.data(), &[0.0, 255.0]);
+        assert_eq!(Gray16Image::from(&gray), buffer);
+    }
+
+    #[test]
+    fn test_rgb_to_luminance() {
+        let rgb = RgbImage::from_pixel(2, 2, Rgb([255, 255, 255]));
+        let gray = GrayImage::from(DynamicImage::ImageRgb8(rgb));
+        assert!(gray.data().iter().all(|&v| v == 255.0));
+    }
+
+    #[test]
+    fn test_rgb_to_color_image() {
+        let rgb = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 51]) });
+        let color = ColorImage::from(&DynamicImage::ImageRgb8(rgb));
+        for (a, b) in color.pixels().iter().flatten().zip([255.0, 0.0, 0.0, 0.0, 0.0, 51.0]) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_save_and_open_formats() {
+        let dir = std::env::temp_dir().join("elsdc_image_io_test");
+        let data: Vec<f64> = (0..48).map(|v| (v * 5) as f64).collect();
+        let gray = GrayImage::from_vec(8, 6, data).unwrap();
+
+        // 无损格式应完全还原
+        for ext in ["png", "bmp", "tiff", "pgm"] {
+            let path = dir.join(format!("image.{}", ext));
+            gray.save(&path).unwrap();
+            assert_eq!(GrayImage::open(&path).unwrap(), gray, "{}", ext);
+        }
+
+        let path = dir.join("image.jpg");
+        gray.save(&path).unwrap();
+        let jpeg = GrayImage::open(&path).unwrap();
+        assert_eq!((jpeg.width(), jpeg.height()), (8, 6));
+        fs::remove_dir_all(dir).ok();
+    }
+}
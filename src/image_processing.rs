@@ -11,11 +11,11 @@
  * ----------		------	---------------------------------------------------------
  **/
  
-use crate::pgm::ensure_pgm_image;
+use crate::gray_image::GrayImage;
 use crate::primitives::Image;
 use crate::ElsdcError;
 use opencv::core::{Mat, MatTraitConst, MatTrait, Vector};
-use opencv::imgcodecs;
+use opencv::{imgcodecs, imgproc};
 use libc::c_double;
 use std::any::Any;
 use crate::elsdc::ImageDouble;
@@ -36,13 +36,64 @@ impl OpenCVImage {
         Ok(Self { mat })
     }
 
+    /// Converts a grayscale, BGR or BGRA `Mat` of any depth into a `CV_64F` grayscale image.
+    ///
+    /// Pixel values are kept as they are (0..255 for 8-bit input), which is
+    /// the range ELSDc's default thresholds are tuned for.
+    pub fn from_mat(mat: &Mat) -> Result<Self, ElsdcError> {
+        if mat.empty() {
+            return Err(ElsdcError::ImageReadError("Empty image".into()));
+        }
+
+        let gray = match mat.channels() {
+            1 => mat.try_clone()?,
+            3 | 4 => {
+                let code = if mat.channels() == 3 { imgproc::COLOR_BGR2GRAY } else { imgproc::COLOR_BGRA2GRAY };
+                let mut gray = Mat::default();
+                imgproc::cvt_color(mat, &mut gray, code, 0)?;
+                gray
+            }
+            n => {
+                return Err(ElsdcError::ImageConversionError(format!(
+                    "Unsupported number of channels: {}",
+                    n
+                )))
+            }
+        };
+
+        let mut mat_64f = Mat::default();
+        gray.convert_to(&mut mat_64f, opencv::core::CV_64F, 1.0, 0.0)?;
+
+        Ok(OpenCVImage { mat: mat_64f })
+    }
+
+    /// Decodes an encoded image (PNG, JPEG, PGM, ...) held in memory.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ElsdcError> {
+        let buf = Vector::<u8>::from_slice(bytes);
+        let mat = imgcodecs::imdecode(&buf, imgcodecs::IMREAD_GRAYSCALE)?;
+        if mat.empty() {
+            return Err(ElsdcError::ImageReadError("Failed to decode image buffer".into()));
+        }
+        Self::from_mat(&mat)
+    }
+
+    /// Whether `mat` is a continuous `CV_64F` buffer that ELSDc can read in place.
+    fn is_dense(&self) -> bool {
+        self.mat.typ() == opencv::core::CV_64F && self.mat.is_continuous() && !self.mat.empty()
+    }
+
+    /// Writes the image as 8-bit grayscale; pixel values are taken to be in
+    /// `0..=255`, as produced by [`OpenCVImage::from_mat`], and saturated.
     pub fn save(&self, path: &str) -> Result<(), ElsdcError> {
         let mut mat_8u = Mat::default();
-        self.mat.convert_to(&mut mat_8u, opencv::core::CV_8U, 255.0, 0.0)
+        self.mat.convert_to(&mut mat_8u, opencv::core::CV_8U, 1.0, 0.0)
             .map_err(|e| ElsdcError::OpenCVError(e))?;
 
-        imgcodecs::imwrite(path, &mat_8u, &Vector::new())
-            .map_err(|e| ElsdcError::OpenCVError(e))?;
+        if !imgcodecs::imwrite(path, &mat_8u, &Vector::new())
+            .map_err(|e| ElsdcError::OpenCVError(e))?
+        {
+            return Err(ElsdcError::ImageConversionError(format!("Failed to write {}", path)));
+        }
 
         Ok(())
     }
@@ -82,23 +133,48 @@ impl TryFrom<*mut ImageDouble> for OpenCVImage {
     }
 }
 
-impl TryFrom<&str> for OpenCVImage {
+impl TryFrom<&GrayImage> for OpenCVImage {
     type Error = ElsdcError;
 
-    fn try_from(path: &str) -> Result<Self, Self::Error> {
-        // 确保输入图像是 PGM 格式
-        let pgm_path = ensure_pgm_image(path)?;
+    fn try_from(gray: &GrayImage) -> Result<Self, Self::Error> {
+        let mut image = OpenCVImage::new(gray.width(), gray.height())?;
+        image.mat.data_typed_mut::<f64>()?.copy_from_slice(gray.data());
+        Ok(image)
+    }
+}
 
-        // 读取 PGM 图像
-        let mat = imgcodecs::imread(&pgm_path, imgcodecs::IMREAD_GRAYSCALE)
-            .map_err(|e| ElsdcError::OpenCVError(e))?;
+impl TryFrom<&OpenCVImage> for GrayImage {
+    type Error = ElsdcError;
 
-        // 将图像转换为 CV_64F 格式（双精度浮点数）
-        let mut mat_64f = Mat::default();
-        mat.convert_to(&mut mat_64f, opencv::core::CV_64F, 1.0, 0.0)
+    fn try_from(image: &OpenCVImage) -> Result<Self, Self::Error> {
+        let (width, height) = (image.width(), image.height());
+        let data = if image.mat.is_continuous() {
+            image.mat.data_typed::<f64>()?.to_vec()
+        } else {
+            let mut data = Vec::with_capacity(width as usize * height as usize);
+            for y in 0..height as i32 {
+                for x in 0..width as i32 {
+                    data.push(*image.mat.at_2d::<f64>(y, x)?);
+                }
+            }
+            data
+        };
+        GrayImage::from_vec(width, height, data)
+    }
+}
+
+impl TryFrom<&str> for OpenCVImage {
+    type Error = ElsdcError;
+
+    fn try_from(path: &str) -> Result<Self, Self::Error> {
+        // 直接在内存中解码，不再写出中间的 PGM 文件
+        let mat = imgcodecs::imread(path, imgcodecs::IMREAD_GRAYSCALE)
             .map_err(|e| ElsdcError::OpenCVError(e))?;
+        if mat.empty() {
+            return Err(ElsdcError::ImageReadError(format!("Failed to read image: {}", path)));
+        }
 
-        Ok(OpenCVImage { mat: mat_64f })
+        OpenCVImage::from_mat(&mat)
     }
 }
 
@@ -110,7 +186,7 @@ impl TryFrom<&String> for OpenCVImage {
     }
 }
 
-impl Image for OpenCVImage {
+unsafe impl Image for OpenCVImage {
     fn width(&self) -> u32 {
         self.mat.cols() as u32
     }
@@ -128,12 +204,35 @@ impl Image for OpenCVImage {
         Ok(*self.mat.at_2d::<f64>(y as i32, x as i32)?)
     }
 
+    // `mat` is public and may hold any depth or be a strided view, so the
+    // buffer is only exposed when it has the layout `Image` promises.
     fn as_ptr(&self) -> *const c_double {
-        self.mat.data() as *const c_double
+        if self.is_dense() {
+            self.mat.data() as *const c_double
+        } else {
+            std::ptr::null()
+        }
     }
 
     fn as_mut_ptr(&mut self) -> *mut f64 {
-        self.mat.data_mut() as *mut f64
+        if self.is_dense() {
+            self.mat.data_mut() as *mut f64
+        } else {
+            std::ptr::null_mut()
+        }
+    }
+
+    fn copy_pixels(&self) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        // `convert_to` allocates a continuous single-channel CV_64F copy
+        if self.mat.channels() != 1 {
+            return Err(Box::new(ElsdcError::ImageConversionError(format!(
+                "Expected a single-channel image, got {} channels",
+                self.mat.channels()
+            ))));
+        }
+        let mut mat_64f = Mat::default();
+        self.mat.convert_to(&mut mat_64f, opencv::core::CV_64F, 1.0, 0.0)?;
+        Ok(mat_64f.data_typed::<f64>()?.to_vec())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -144,3 +243,27 @@ impl Image for OpenCVImage {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_round_trip() {
+        let values = [0.0, 1.0, 37.0, 128.0, 200.0, 255.0];
+        let mut image = OpenCVImage::new(3, 2).unwrap();
+        for (i, &v) in values.iter().enumerate() {
+            image.set_pixel(i as u32 % 3, i as u32 / 3, v).unwrap();
+        }
+
+        let path = std::env::temp_dir().join("elsdc_opencv_save_test.pgm");
+        let path = path.to_string_lossy();
+        image.save(&path).unwrap();
+        let loaded = OpenCVImage::try_from(&*path).unwrap();
+        for (i, &v) in values.iter().enumerate() {
+            // 保存再读取后像素值应保持不变
+            assert_eq!(loaded.get_pixel(i as u32 % 3, i as u32 / 3).unwrap(), v);
+        }
+        std::fs::remove_file(&*path).ok();
+    }
+}
@@ -0,0 +1,253 @@
+/**
+ * File: /src/label_map.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 11:05:12 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+#[cfg(feature = "opencv")]
+use opencv::core::{Mat, MatTrait, Scalar, Vec3b, VecN, Vector};
+#[cfg(feature = "opencv")]
+use opencv::imgcodecs;
+
+use crate::pgm;
+use crate::ElsdcError;
+
+/// Per-pixel map of the primitive that each edge pixel supports.
+///
+/// ELSDc writes the label of the supporting primitive into every pixel it used;
+/// all other pixels keep [`LabelMap::BACKGROUND`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelMap {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major labels, `width * height` entries.
+    pub data: Vec<i32>,
+}
+
+impl LabelMap {
+    /// Label of pixels that support no primitive.
+    pub const BACKGROUND: i32 = 0;
+
+    pub fn new(width: u32, height: u32, data: Vec<i32>) -> Result<Self, ElsdcError> {
+        if data.len() != (width as usize) * (height as usize) {
+            return Err(ElsdcError::ImageConversionError(format!(
+                "Label data has {} entries, expected {}x{}",
+                data.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self { width, height, data })
+    }
+
+    /// Label at `(x, y)`, or `None` outside the map.
+    pub fn get(&self, x: u32, y: u32) -> Option<i32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.data[(y as usize) * (self.width as usize) + x as usize])
+    }
+
+    /// Coordinates `(x, y)` of every pixel carrying `label`.
+    pub fn pixels_of(&self, label: i32) -> Vec<(u32, u32)> {
+        let width = self.width.max(1) as usize;
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, &l)| l == label)
+            .map(|(i, _)| ((i % width) as u32, (i / width) as u32))
+            .collect()
+    }
+
+    /// Number of pixels carrying `label`.
+    pub fn count(&self, label: i32) -> usize {
+        self.data.iter().filter(|&&l| l == label).count()
+    }
+
+    /// Number of supporting pixels for every label present in the map.
+    pub fn counts(&self) -> BTreeMap<i32, usize> {
+        let mut counts = BTreeMap::new();
+        for &l in self.data.iter().filter(|&&l| l != Self::BACKGROUND) {
+            *counts.entry(l).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Renders the map as interleaved RGB bytes, one colour per label on a black background.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|&label| {
+                if label == Self::BACKGROUND {
+                    [0, 0, 0]
+                } else {
+                    label_color(label)
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the map as a BGR `Mat`, one colour per label on a black background.
+    #[cfg(feature = "opencv")]
+    pub fn to_color_mat(&self) -> Result<Mat, ElsdcError> {
+        let mut mat = Mat::new_rows_cols_with_default(
+            self.height as i32,
+            self.width as i32,
+            opencv::core::CV_8UC3,
+            Scalar::all(0.0),
+        )?;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let label = self.data[(y as usize) * (self.width as usize) + x as usize];
+                if label != Self::BACKGROUND {
+                    let [r, g, b] = label_color(label);
+                    *mat.at_2d_mut::<Vec3b>(y as i32, x as i32)? = VecN([b, g, r]);
+                }
+            }
+        }
+
+        Ok(mat)
+    }
+
+    /// Saves the colour-coded map; the format follows the extension (e.g. `.png`).
+    ///
+    /// `.ppm` is always available; other formats are encoded by OpenCV or,
+    /// without it, by the `image` crate.
+    pub fn save(&self, path: &str) -> Result<(), ElsdcError> {
+        let path = Path::new(path);
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ppm")) {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut writer = BufWriter::new(File::create(path)?);
+            pgm::write_ppm(&mut writer, &self.to_rgb(), self.width as usize, self.height as usize)?;
+            return Ok(());
+        }
+
+        #[cfg(feature = "opencv")]
+        {
+            let mat = self.to_color_mat()?;
+            if !imgcodecs::imwrite(&path.to_string_lossy(), &mat, &Vector::new())? {
+                return Err(ElsdcError::ImageConversionError(format!("Failed to write {}", path.display())));
+            }
+            Ok(())
+        }
+        #[cfg(all(not(feature = "opencv"), feature = "image"))]
+        {
+            crate::image_io::save_rgb(self.to_rgb(), self.width, self.height, path)
+        }
+        #[cfg(not(any(feature = "opencv", feature = "image")))]
+        {
+            Err(ElsdcError::ConfigError(format!(
+                "{} needs OpenCV or the `image` feature; save the label map as .ppm instead",
+                path.display()
+            )))
+        }
+    }
+}
+
+/// Well separated, deterministic RGB colour for a label (golden-angle hue walk).
+pub(crate) fn label_color(label: i32) -> [u8; 3] {
+    hue_color(label as f64 * 137.507_764)
+}
+
+/// Saturated RGB colour of the given hue in degrees.
+pub(crate) fn hue_color(hue: f64) -> [u8; 3] {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let (s, v) = (0.85, 1.0);
+    let c = v * s;
+    let x = c * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    let to_u8 = |v: f64| ((v + m) * 255.0).round() as u8;
+    [to_u8(r), to_u8(g), to_u8(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_map() -> LabelMap {
+        #[rustfmt::skip]
+        let data = vec![
+            0, 1, 1,
+            2, 0, 1,
+            2, 2, 0,
+        ];
+        LabelMap::new(3, 3, data).unwrap()
+    }
+
+    #[test]
+    fn test_pixels_of() {
+        let map = sample_map();
+        assert_eq!(map.pixels_of(1), vec![(1, 0), (2, 0), (2, 1)]);
+        assert_eq!(map.pixels_of(2), vec![(0, 1), (0, 2), (1, 2)]);
+        assert!(map.pixels_of(3).is_empty());
+    }
+
+    #[test]
+    fn test_counts() {
+        let map = sample_map();
+        assert_eq!(map.count(1), 3);
+        assert_eq!(map.count(LabelMap::BACKGROUND), 3);
+        let counts = map.counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&2], 3);
+    }
+
+    #[test]
+    fn test_to_rgb() {
+        let rgb = sample_map().to_rgb();
+        assert_eq!(rgb.len(), 27);
+        assert_eq!(&rgb[0..3], &[0, 0, 0]);
+        assert_eq!(&rgb[3..6], &label_color(1));
+        assert_ne!(label_color(1), label_color(2));
+    }
+
+    #[test]
+    fn test_save_ppm() {
+        let path = std::env::temp_dir().join("elsdc_label_map_test/labels.ppm");
+        sample_map().save(&path.to_string_lossy()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let header = b"P6\n3 3\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(&bytes[header.len()..], sample_map().to_rgb().as_slice());
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    #[cfg(all(feature = "image", not(feature = "opencv")))]
+    fn test_save_png_without_opencv() {
+        let path = std::env::temp_dir().join("elsdc_label_map_test/labels.png");
+        sample_map().save(&path.to_string_lossy()).unwrap();
+        let decoded = image::open(&path).unwrap().to_rgb8();
+        assert_eq!(decoded.into_raw(), sample_map().to_rgb());
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        assert!(LabelMap::new(2, 2, vec![0; 3]).is_err());
+        assert_eq!(sample_map().get(3, 0), None);
+    }
+}
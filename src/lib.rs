@@ -1,14 +1,49 @@
+pub mod batch;
+pub mod color;
 pub mod elsdc;
 pub mod error;
+pub mod eval;
+pub mod export;
+mod geometry;
+pub mod gray_image;
+#[cfg(feature = "image")]
+pub mod image_io;
+#[cfg(feature = "opencv")]
 pub mod image_processing;
+pub mod label_map;
+#[cfg(feature = "ndarray")]
+pub mod ndarray_image;
 pub mod pgm;
+pub mod polygon;
 pub mod primitives;
+pub mod pyramid;
+#[cfg(feature = "opencv")]
+pub mod render;
 pub mod ring;
+pub mod roi;
+pub mod sink;
+pub mod svg;
+pub mod tiling;
 mod util;
 
-pub use elsdc::detect_primitives;
+pub use color::{Channel, ColorImage, GrayConversion};
+pub use elsdc::{detect_primitives, Detection, Detector};
 pub use error::ElsdcError;
+pub use eval::{evaluate, Evaluation, RingMatch};
+pub use export::{DetectionReport, ExportFormat, ImageInfo};
+pub use gray_image::GrayImage;
+#[cfg(feature = "opencv")]
 pub use image_processing::OpenCVImage;
-pub use primitives::{Image, Primitive};
-pub use ring::Ring;
+pub use label_map::LabelMap;
+pub use pgm::NetpbmRows;
+pub use polygon::{PointD, Polygon};
+pub use primitives::{DetectedPrimitive, Image, Primitive};
+pub use pyramid::{PyramidConfig, PyramidDetection};
+#[cfg(feature = "opencv")]
+pub use render::{ColorBy, RenderStyle, Renderer};
+pub use ring::{BoundingBox, IouMethod, Ring, SimilarityMetric, SparseMatrix};
+pub use roi::{Mask, Roi};
+pub use sink::{CallbackSink, FileSink, MemorySink, ResultSink, SinkRecord, StdoutSink, WriterSink};
+pub use svg::{SvgBackground, SvgDocument, SvgStyle};
+pub use tiling::{TileConfig, TileSource, TiledDetection};
 pub use util::save_matrix_to_file;
@@ -3,7 +3,7 @@
  * Created Date: Monday, July 22nd 2024
  * Author: Zihan
  * -----
- * Last Modified: Thursday, 8th August 2024 8:48:14 am
+ * Last Modified: Sunday, 18th October 2026 3:41:09 am
  * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
  * -----
  * HISTORY:
@@ -11,19 +11,78 @@
  * ----------		------	---------------------------------------------------------
 **/
 
-use elsdc::{ElsdcError, OpenCVImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use elsdc::batch::{self, BatchCounts};
+use elsdc::{Channel, Detection, DetectionReport, Detector, ElsdcError, ExportFormat, FileSink, GrayConversion, IouMethod, Mask, Primitive, ResultSink, Roi, SimilarityMetric, StdoutSink, SvgBackground, SvgDocument};
 use env_logger::Env;
 use log::{info, error};
-use clap::Parser;
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
 use elsdc::save_matrix_to_file;
 use elsdc::ring::Ring;
+#[cfg(feature = "opencv")]
+use elsdc::{ColorBy, RenderStyle, Renderer};
+
+// Without OpenCV the CLI reads and writes PGM through the pure-Rust backend.
+#[cfg(feature = "opencv")]
+type CliImage = elsdc::OpenCVImage;
+#[cfg(not(feature = "opencv"))]
+type CliImage = elsdc::GrayImage;
+
+#[cfg(feature = "opencv")]
+const DEFAULT_OUTPUT: &str = "result/output_all_rings.png";
+#[cfg(not(feature = "opencv"))]
+const DEFAULT_OUTPUT: &str = "result/output_all_rings.pgm";
+
+/// Extension of the overlays written by `batch`.
+#[cfg(feature = "opencv")]
+const OVERLAY_EXTENSION: &str = "png";
+#[cfg(not(feature = "opencv"))]
+const OVERLAY_EXTENSION: &str = "pgm";
+
+/// Mask pixels above this value are searched.
+const MASK_THRESHOLD: f64 = 127.5;
+
+/// Output path meaning standard output.
+const STDOUT: &str = "-";
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    #[clap(flatten)]
+    args: Args,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Detect primitives and write them as structured results
+    Detect(DetectArgs),
+    /// Draw detected (or previously saved) primitives over the image
+    Draw(DrawArgs),
+    /// Print the pairwise similarity matrix of the detected rings
+    Iou(IouArgs),
+    /// Compare detected rings with ground truth
+    Eval(EvalArgs),
+    /// Convert an image, or a results file between formats
+    Convert(ConvertArgs),
+    /// Time repeated detections on one image
+    Bench(BenchArgs),
+    /// Detect primitives in every image of a directory or glob pattern
+    Batch(BatchArgs),
+}
+
+#[derive(ClapArgs, Debug)]
 struct Args {
     /// Input image file
-    #[clap(value_parser)]
-    input: String,
+    #[clap(value_parser, required = true)]
+    input: Option<String>,
 
     /// Output image file
     #[clap(short, long, value_parser)]
@@ -32,47 +91,750 @@ struct Args {
     /// Verbose mode
     #[clap(short, long)]
     verbose: bool,
+
+    /// Read the input in colour and reduce it to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Only search this rectangle, given as x,y,width,height
+    #[clap(long, value_parser = parse_roi)]
+    roi: Option<Roi>,
+
+    /// Only search the bright pixels of this mask image (same size as the input)
+    #[clap(long, value_parser, conflicts_with = "roi")]
+    mask: Option<String>,
+
+    #[clap(flatten)]
+    metric: MetricArgs,
+
+    /// Write every detected primitive and the image metadata in this format
+    #[clap(long, value_enum)]
+    format: Option<Format>,
+
+    /// Destination of the structured results (`-` for stdout)
+    #[clap(long, value_parser, requires = "format")]
+    results: Option<String>,
+
+    /// Also write one line per primitive to this file (`-` for stdout)
+    #[clap(long, value_parser)]
+    log: Option<String>,
+}
+
+// Input image of the single-image commands and where to search it.
+#[derive(ClapArgs, Debug)]
+struct SourceArgs {
+    /// Input image file
+    #[clap(value_parser)]
+    input: String,
+
+    /// Read the input in colour and reduce it to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Only search this rectangle, given as x,y,width,height
+    #[clap(long, value_parser = parse_roi)]
+    roi: Option<Roi>,
+
+    /// Only search the bright pixels of this mask image (same size as the input)
+    #[clap(long, value_parser, conflicts_with = "roi")]
+    mask: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+struct DetectArgs {
+    #[clap(flatten)]
+    source: SourceArgs,
+
+    /// Results file, `-` for stdout
+    #[clap(short, long, value_parser, default_value = STDOUT)]
+    output: String,
+
+    /// Format of the results
+    #[clap(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Also write one line per primitive to this file (`-` for stdout)
+    #[clap(long, value_parser)]
+    log: Option<String>,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+}
+
+#[derive(ClapArgs, Debug)]
+struct DrawArgs {
+    #[clap(flatten)]
+    source: SourceArgs,
+
+    /// Output image file; an `.svg` extension writes a vector overlay instead
+    #[clap(short, long, value_parser, default_value = DEFAULT_OUTPUT)]
+    output: String,
+
+    /// Draw the primitives of this JSON or NDJSON results file instead of detecting
+    #[clap(long, value_parser, conflicts_with_all = ["roi", "mask"])]
+    results: Option<String>,
+
+    /// Background of SVG output
+    #[clap(long, value_enum, default_value_t = Background::Embed)]
+    background: Background,
+
+    /// Leave the polygons out of SVG output
+    #[clap(long)]
+    no_polygons: bool,
+
+    #[cfg(feature = "opencv")]
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+}
+
+#[derive(ClapArgs, Debug)]
+struct IouArgs {
+    /// Input image, or a JSON/NDJSON results file whose rings are used as is
+    #[clap(value_parser)]
+    input: String,
+
+    /// Matrix file, `-` for stdout
+    #[clap(short, long, value_parser, default_value = STDOUT)]
+    output: String,
+
+    #[clap(flatten)]
+    metric: MetricArgs,
+
+    /// Read the input in colour and reduce it to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+}
+
+#[derive(ClapArgs, Debug)]
+struct EvalArgs {
+    /// Detected rings: an image to run the detector on, or a JSON/NDJSON results file
+    #[clap(value_parser)]
+    detected: String,
+
+    /// Ground-truth rings as a JSON/NDJSON results file
+    #[clap(value_parser)]
+    truth: String,
+
+    /// Minimum similarity for a detected ring to match a ground-truth ring
+    #[clap(long, value_parser, default_value_t = 0.5)]
+    threshold: f64,
+
+    /// Evaluation report (JSON), `-` for stdout
+    #[clap(short, long, value_parser, default_value = STDOUT)]
+    output: String,
+
+    #[clap(flatten)]
+    metric: MetricArgs,
+
+    /// Read the input in colour and reduce it to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+}
+
+#[derive(ClapArgs, Debug)]
+struct ConvertArgs {
+    /// Image to convert, or a JSON/NDJSON results file
+    #[clap(value_parser)]
+    input: String,
+
+    /// Destination; its extension selects the image or results format
+    #[clap(value_parser)]
+    output: String,
+
+    /// Read the input in colour and reduce it to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+struct BenchArgs {
+    #[clap(flatten)]
+    source: SourceArgs,
+
+    /// Number of timed detections
+    #[clap(long, value_parser, default_value_t = 10)]
+    runs: usize,
+
+    /// Untimed detections run first
+    #[clap(long, value_parser, default_value_t = 1)]
+    warmup: usize,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+}
+
+#[derive(ClapArgs, Debug)]
+struct BatchArgs {
+    /// Directory (searched recursively) or glob pattern such as `images/**/*.png`
+    #[clap(value_parser)]
+    input: String,
+
+    /// Directory receiving the overlays, results and summary.json; mirrors the input tree
+    #[clap(short, long, value_parser, default_value = "result/batch")]
+    output: PathBuf,
+
+    /// Number of images processed in parallel (default: one per CPU)
+    #[clap(short, long, value_parser)]
+    jobs: Option<usize>,
+
+    /// Format of the per-image results
+    #[clap(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+
+    /// Verbose mode
+    #[clap(short, long)]
+    verbose: bool,
+
+    /// Read the inputs in colour and reduce them to grayscale this way
+    #[clap(long, value_enum)]
+    gray: Option<Gray>,
+
+    /// Only search this rectangle of every image, given as x,y,width,height
+    #[clap(long, value_parser = parse_roi)]
+    roi: Option<Roi>,
+
+    /// Only search the bright pixels of this mask image (same size as every input)
+    #[clap(long, value_parser, conflicts_with = "roi")]
+    mask: Option<String>,
+}
+
+// Colours and annotations of raster overlays.
+#[cfg(feature = "opencv")]
+#[derive(ClapArgs, Debug)]
+struct RenderArgs {
+    /// How primitives are coloured
+    #[clap(long, value_enum, default_value_t = ColorMode::Index)]
+    color_by: ColorMode,
+
+    /// Line thickness in pixels
+    #[clap(long, value_parser, default_value_t = 1)]
+    thickness: i32,
+
+    /// Draw 8-connected instead of anti-aliased lines
+    #[clap(long)]
+    no_antialias: bool,
+
+    /// Mark the centre of every ring with a cross
+    #[clap(long)]
+    centers: bool,
+
+    /// Draw the semi-axes of every ring
+    #[clap(long)]
+    axes: bool,
+
+    /// Mark the arc endpoints
+    #[clap(long)]
+    endpoints: bool,
+
+    /// Write the index of every primitive next to it
+    #[clap(long)]
+    labels: bool,
+}
+
+// Similarity between rings, shared by the commands comparing them.
+#[derive(ClapArgs, Debug)]
+struct MetricArgs {
+    /// Similarity between rings
+    #[clap(long, value_enum, default_value_t = Metric::Iou)]
+    metric: Metric,
+
+    /// Tolerance band (in pixels) of the arc-overlap metric
+    #[clap(long, value_parser, default_value_t = 2.0)]
+    arc_tolerance: f64,
+}
+
+fn parse_roi(s: &str) -> Result<Roi, String> {
+    s.parse().map_err(|e: ElsdcError| e.to_string())
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Metric {
+    /// Analytic intersection over union of the ring regions
+    Iou,
+    /// Rasterised intersection over union (reference implementation)
+    IouRaster,
+    /// Fraction of the two curves lying within the tolerance band of each other
+    ArcOverlap,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Gray {
+    /// BT.601 weighted sum of the channels
+    Luminance,
+    /// Red channel only
+    Red,
+    /// Green channel only
+    Green,
+    /// Blue channel only
+    Blue,
+    /// The channel with the strongest edges
+    MaxGradient,
+    /// HSV value (largest channel)
+    Value,
+}
+
+impl From<Gray> for GrayConversion {
+    fn from(gray: Gray) -> Self {
+        match gray {
+            Gray::Luminance => GrayConversion::BT601,
+            Gray::Red => GrayConversion::Channel(Channel::Red),
+            Gray::Green => GrayConversion::Channel(Channel::Green),
+            Gray::Blue => GrayConversion::Channel(Channel::Blue),
+            Gray::MaxGradient => GrayConversion::MaxGradient,
+            Gray::Value => GrayConversion::Value,
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ColorMode {
+    /// A distinct colour per primitive
+    Index,
+    /// One colour per kind of primitive
+    Type,
+    /// Rings from blue to red by angular coverage
+    Coverage,
+    /// Rings from blue to red by arc length
+    Length,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Background {
+    /// Store the input image in the SVG (re-encoded as PNG if browsers cannot show it)
+    Embed,
+    /// Reference the input image by its absolute path
+    Link,
+    /// No background
+    None,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// A single JSON document
+    Json,
+    /// One row per primitive
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl From<Format> for ExportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => ExportFormat::Json,
+            Format::Csv => ExportFormat::Csv,
+            Format::Ndjson => ExportFormat::Ndjson,
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl RenderArgs {
+    fn style(&self) -> RenderStyle {
+        RenderStyle {
+            color_by: match self.color_by {
+                ColorMode::Index => ColorBy::Index,
+                ColorMode::Type => ColorBy::Type,
+                ColorMode::Coverage => ColorBy::ArcCoverage,
+                ColorMode::Length => ColorBy::ArcLength,
+            },
+            thickness: self.thickness,
+            antialias: !self.no_antialias,
+            centers: self.centers,
+            axes: self.axes,
+            endpoints: self.endpoints,
+            labels: self.labels,
+            ..RenderStyle::default()
+        }
+    }
+}
+
+impl MetricArgs {
+    fn similarity_metric(&self) -> SimilarityMetric {
+        match self.metric {
+            Metric::Iou => SimilarityMetric::Iou(IouMethod::Analytic),
+            Metric::IouRaster => SimilarityMetric::Iou(IouMethod::Raster),
+            Metric::ArcOverlap => SimilarityMetric::ArcOverlap { tolerance: self.arc_tolerance },
+        }
+    }
 }
 
 fn main() -> Result<(), ElsdcError> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
-    let args = Args::parse();
-    log::set_max_level(if args.verbose { log::LevelFilter::Debug } else { log::LevelFilter::Info });
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Detect(args)) => run_detect(args),
+        Some(Command::Draw(args)) => run_draw(args),
+        Some(Command::Iou(args)) => run_iou(args),
+        Some(Command::Eval(args)) => run_eval(args),
+        Some(Command::Convert(args)) => run_convert(args),
+        Some(Command::Bench(args)) => run_bench(args),
+        Some(Command::Batch(args)) => run_batch(args),
+        None => run_single(cli.args),
+    }
+}
 
-    info!("Processing image: {}", args.input);
+fn set_verbose(verbose: bool) {
+    log::set_max_level(if verbose { log::LevelFilter::Debug } else { log::LevelFilter::Info });
+}
 
-    let (primitives, compatibility_matrix) = match elsdc::elsdc::detect_primitives_on_real_image(&args.input) {
-        Ok(result) => result,
-        Err(e) => {
-            error!("Failed to detect primitives: {:?}", e);
-            return Err(e);
-        }
-    };
+fn load_image(path: &str, gray: Option<Gray>) -> Result<CliImage, ElsdcError> {
+    match gray {
+        Some(gray) => CliImage::open_with(path, gray.into()),
+        None => CliImage::try_from(path),
+    }
+}
+
+/// Runs the detector on `image`, restricted to the ROI or mask if one was given.
+fn detect_in(
+    detector: &Detector,
+    image: &CliImage,
+    roi: Option<Roi>,
+    mask: Option<&Mask>,
+) -> Result<Detection, ElsdcError> {
+    if let Some(roi) = roi {
+        detector.detect_roi(image, roi)
+    } else if let Some(mask) = mask {
+        detector.detect_masked(image, mask)
+    } else {
+        detector.detect(image)
+    }
+}
+
+/// Reads the `--mask` image, if one was given.
+fn load_mask(path: Option<&str>) -> Result<Option<Mask>, ElsdcError> {
+    path.map(|path| Mask::from_image(&CliImage::try_from(path)?, MASK_THRESHOLD)).transpose()
+}
 
+/// Whether `path` names a results file that can be read back.
+fn is_results_file(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| {
+        ["json", "ndjson", "jsonl"].iter().any(|e| ext.eq_ignore_ascii_case(e))
+    })
+}
+
+/// Rings of a results file, or of a fresh detection if `path` is an image.
+fn load_rings(path: &str, detector: &Detector, gray: Option<Gray>) -> Result<Vec<Ring>, ElsdcError> {
+    if is_results_file(path) {
+        return Ok(DetectionReport::load(path)?.rings());
+    }
+    let detection = detector.detect(&load_image(path, gray)?)?;
+    info!("{}: detected {} rings", path, detection.rings.len());
+    Ok(detection.rings)
+}
+
+/// Line-per-primitive log at `path`; `-` is stdout.
+fn result_sink(path: &str) -> Result<Box<dyn ResultSink>, ElsdcError> {
+    if path == STDOUT {
+        return Ok(Box::new(StdoutSink::stdout()));
+    }
+    Ok(Box::new(FileSink::create(path)?))
+}
+
+/// Opens `path` for writing, creating parent directories; `-` is stdout.
+fn create_output(path: &str) -> Result<Box<dyn Write>, ElsdcError> {
+    if path == STDOUT {
+        return Ok(Box::new(std::io::stdout().lock()));
+    }
+    if let Some(parent) = Path::new(path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(Box::new(BufWriter::new(File::create(path)?)))
+}
+
+fn run_detect(args: DetectArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    let detector = Detector::new();
+    let source = &args.source;
+
+    let image = load_image(&source.input, source.gray)?;
+    let mask = load_mask(source.mask.as_deref())?;
+    let detection = detect_in(&detector, &image, source.roi, mask.as_ref())?;
     info!(
-        "Detection successful! Found {} primitives",
-        primitives.len()
+        "{}: {} rings, {} polygons",
+        source.input,
+        detection.rings.len(),
+        detection.polygons.len()
     );
+    if let Some(log) = &args.log {
+        result_sink(log)?.record_detection(&detection)?;
+    }
+    DetectionReport::new(&detection)
+        .with_path(&source.input)
+        .write(create_output(&args.output)?, args.format.into())
+}
 
-    // 计算和输出IoU矩阵
-    let rings: Vec<&Ring> = primitives.iter().filter_map(|p| p.as_any().downcast_ref::<Ring>()).collect();
-    if rings.is_empty() {
-        error!("No rings detected.");
+fn run_draw(args: DrawArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    let detector = Detector::new();
+    let source = &args.source;
+
+    let report = match &args.results {
+        Some(results) => DetectionReport::load(results)?,
+        None => {
+            let image = load_image(&source.input, source.gray)?;
+            let mask = load_mask(source.mask.as_deref())?;
+            DetectionReport::new(&detect_in(&detector, &image, source.roi, mask.as_ref())?)
+        }
+    };
+
+    let output = Path::new(&args.output);
+    if output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("svg")) {
+        let mut document = SvgDocument::from_report(&report);
+        document.style.polygons = !args.no_polygons;
+        document.background = match args.background {
+            Background::Embed => Some(SvgBackground::embed(&source.input)?),
+            Background::Link => Some(SvgBackground::Link(
+                std::fs::canonicalize(&source.input)?.to_string_lossy().into_owned(),
+            )),
+            Background::None => None,
+        };
+        document.save(output)?;
+        info!("Wrote {} rings and {} polygons to {}", report.rings.len(), report.polygons.len(), args.output);
         return Ok(());
     }
 
-    let iou_matrix = Ring::generate_compatibility_matrix(&rings.iter().map(|&r| *r).collect::<Vec<Ring>>());
+    #[cfg(feature = "opencv")]
+    {
+        let mut renderer = Renderer::open(&source.input, args.render.style())?;
+        let polygons: Vec<_> = report.polygons.iter().map(|record| record.polygon.clone()).collect();
+        renderer.draw(&report.rings(), &polygons)?;
+        renderer.save(output)?;
+    }
+    #[cfg(not(feature = "opencv"))]
+    {
+        let mut image = load_image(&source.input, source.gray)?;
+        for primitive in report.primitives() {
+            primitive.draw(&mut image)?;
+        }
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        image.save(&args.output)?;
+    }
+    info!("Drew {} rings and {} polygons to {}", report.rings.len(), report.polygons.len(), args.output);
+    Ok(())
+}
+
+fn run_iou(args: IouArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    let detector = Detector::new();
+    let rings = load_rings(&args.input, &detector, args.gray)?;
+    let matrix = Ring::generate_compatibility_matrix_with(&rings, args.metric.similarity_metric())?;
 
-    println!("IoU Matrix:");
-    for row in &iou_matrix {
+    if args.output != STDOUT {
+        if let Some(parent) = Path::new(&args.output).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        save_matrix_to_file(&matrix, &args.output)?;
+        info!("Saved {}x{} matrix to {}", rings.len(), rings.len(), args.output);
+        return Ok(());
+    }
+    let mut out = std::io::stdout().lock();
+    for row in &matrix {
         for value in row {
-            print!("{:.2} ", value);
+            write!(out, "{:.4} ", value)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn run_eval(args: EvalArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    if !is_results_file(&args.truth) {
+        return Err(ElsdcError::ConfigError(format!(
+            "Ground truth must be a JSON or NDJSON results file: {}",
+            args.truth
+        )));
+    }
+    let detector = Detector::new();
+    let detected = load_rings(&args.detected, &detector, args.gray)?;
+    let truth = DetectionReport::load(&args.truth)?.rings();
+
+    let evaluation = elsdc::evaluate(&detected, &truth, args.metric.similarity_metric(), args.threshold)?;
+    info!(
+        "precision {:.3}, recall {:.3}, F1 {:.3} ({} detected, {} ground truth)",
+        evaluation.precision,
+        evaluation.recall,
+        evaluation.f1,
+        evaluation.detected,
+        evaluation.truth
+    );
+    let mut out = create_output(&args.output)?;
+    serde_json::to_writer_pretty(&mut out, &evaluation).map_err(std::io::Error::from)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn run_convert(args: ConvertArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    if is_results_file(&args.input) {
+        let format: ExportFormat = Path::new(&args.output)
+            .extension()
+            .map(|ext| ext.to_string_lossy().parse())
+            .transpose()?
+            .ok_or_else(|| ElsdcError::ConfigError(format!("No results format for {}", args.output)))?;
+        DetectionReport::load(&args.input)?.save(&args.output, format)?;
+    } else {
+        let image = load_image(&args.input, args.gray)?;
+        if let Some(parent) = Path::new(&args.output).parent() {
+            std::fs::create_dir_all(parent)?;
         }
-        println!();
+        image.save(&args.output)?;
+    }
+    info!("Converted {} to {}", args.input, args.output);
+    Ok(())
+}
+
+fn run_bench(args: BenchArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    if args.runs == 0 {
+        return Err(ElsdcError::ConfigError("bench needs at least one run".into()));
+    }
+    let detector = Detector::new();
+    let source = &args.source;
+    let image = load_image(&source.input, source.gray)?;
+    let mask = load_mask(source.mask.as_deref())?;
+    let detect = || detect_in(&detector, &image, source.roi, mask.as_ref());
+
+    for _ in 0..args.warmup {
+        detect()?;
     }
+    let mut times = Vec::with_capacity(args.runs);
+    let mut detection = None;
+    for _ in 0..args.runs {
+        let start = Instant::now();
+        detection = Some(detect()?);
+        times.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    let detection = detection.expect("at least one run");
+    times.sort_by(f64::total_cmp);
+    let n = times.len();
+    let median = if n % 2 == 1 { times[n / 2] } else { (times[n / 2 - 1] + times[n / 2]) / 2.0 };
+
+    let summary = serde_json::json!({
+        "input": source.input,
+        "width": detection.labels.width,
+        "height": detection.labels.height,
+        "rings": detection.rings.len(),
+        "polygons": detection.polygons.len(),
+        "runs": n,
+        "min_ms": times[0],
+        "median_ms": median,
+        "mean_ms": times.iter().sum::<f64>() / n as f64,
+        "max_ms": times[n - 1],
+    });
+    println!("{}", summary);
+    Ok(())
+}
+
+fn run_batch(args: BatchArgs) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    let detector = Detector::new();
+    let format = ExportFormat::from(args.format);
+    let mask = load_mask(args.mask.as_deref())?;
 
-    // Load the image again for drawing (since process_image doesn't return the image)
-    let mut image = match OpenCVImage::try_from(&args.input) {
+    let inputs = batch::find_images(&args.input)?;
+    if inputs.is_empty() {
+        return Err(ElsdcError::ConfigError(format!("No images found for {}", args.input)));
+    }
+    info!("Processing {} images into {}", inputs.len(), args.output.display());
+
+    let summary = batch::run_batch(&inputs, args.jobs.unwrap_or(0), |input| {
+        let image = load_image(&input.path.to_string_lossy(), args.gray)?;
+        let detection = detect_in(&detector, &image, args.roi, mask.as_ref())?;
+        DetectionReport::new(&detection)
+            .with_path(&input.path)
+                .save(input.output_path(&args.output, format.extension()), format)?;
+
+        let overlay = input.output_path(&args.output, OVERLAY_EXTENSION);
+        #[cfg(feature = "opencv")]
+        {
+            let mut renderer = Renderer::new(&image, RenderStyle::default())?;
+            renderer.draw(&detection.rings, &detection.polygons)?;
+            renderer.save(&overlay)?;
+        }
+        #[cfg(not(feature = "opencv"))]
+        {
+            let mut image = image;
+            for primitive in detection.primitives() {
+                primitive.draw(&mut image)?;
+            }
+            if let Some(parent) = overlay.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            image.save(overlay.to_string_lossy().as_ref())?;
+        }
+
+        info!("{}: {} rings, {} polygons", input.path.display(), detection.rings.len(), detection.polygons.len());
+        Ok(BatchCounts { rings: detection.rings.len(), polygons: detection.polygons.len() })
+    })?;
+
+    let summary_path = args.output.join("summary.json");
+    summary.save(&summary_path)?;
+    for entry in summary.failures() {
+        error!("Failed to process {}: {}", entry.input, entry.error.as_deref().unwrap_or_default());
+    }
+    info!(
+        "Processed {} images ({} failed) in {:.0} ms; summary written to {}",
+        summary.images,
+        summary.failed,
+        summary.runtime_ms,
+        summary_path.display()
+    );
+    if summary.failed > 0 {
+        return Err(ElsdcError::DetectionError(format!(
+            "{} of {} images failed",
+            summary.failed, summary.images
+        )));
+    }
+    Ok(())
+}
+
+/// `<stem><suffix>` in the directory of `output`, e.g. `out/a.jpg` ->
+/// `out/a_matrix.txt` for the suffix `_matrix.txt`.
+fn derived_path(output: &str, suffix: &str) -> String {
+    let path = Path::new(output);
+    let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!("{}{}", stem, suffix)).to_string_lossy().into_owned()
+}
+
+fn run_single(args: Args) -> Result<(), ElsdcError> {
+    set_verbose(args.verbose);
+    let input = args.input.clone().expect("clap requires the input without a subcommand");
+
+    info!("Processing image: {}", input);
+
+    let detector = Detector::new();
+
+    // Load the image once; it is reused below for drawing
+    let mut image = match load_image(&input, args.gray) {
         Ok(img) => {
             img
         }
@@ -82,6 +844,57 @@ fn main() -> Result<(), ElsdcError> {
         }
     };
 
+    let mask = load_mask(args.mask.as_deref())?;
+    let detection = match detect_in(&detector, &image, args.roi, mask.as_ref()) {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to detect primitives: {:?}", e);
+            return Err(e);
+        }
+    };
+    if let Some(format) = args.format {
+        let format = ExportFormat::from(format);
+        let report = DetectionReport::new(&detection).with_path(&input);
+        let results = args.results.clone().unwrap_or_else(|| {
+            args.output.as_ref()
+                .map(|s| derived_path(s, &format!("_detections.{}", format.extension())))
+                .unwrap_or_else(|| format!("result/detections.{}", format.extension()))
+        });
+        if results == STDOUT {
+            report.write(std::io::stdout().lock(), format)?;
+        } else {
+            report.save(&results, format)?;
+            info!("Saved detection results to {}", results);
+        }
+    }
+    if let Some(log) = &args.log {
+        result_sink(log)?.record_detection(&detection)?;
+    }
+    let primitives = detection.primitives();
+
+    info!(
+        "Detection successful! Found {} primitives",
+        primitives.len()
+    );
+
+    if detection.rings.is_empty() {
+        error!("No rings detected.");
+        return Ok(());
+    }
+    // 矩阵只计算一次，打印和保存共用
+    let compatibility_matrix = detection.compatibility_matrix_with(args.metric.similarity_metric())?;
+
+    // 结果写到 stdout 时不再打印矩阵
+    if args.results.as_deref() != Some(STDOUT) {
+        println!("IoU Matrix:");
+        for row in &compatibility_matrix {
+            for value in row {
+                print!("{:.2} ", value);
+            }
+            println!();
+        }
+    }
+
     // Draw primitives
     for primitive in &primitives {
         if let Err(e) = primitive.draw(&mut image) {
@@ -90,11 +903,11 @@ fn main() -> Result<(), ElsdcError> {
     }
     // debug log, primitive numbers and details
     for (i, primitive) in primitives.iter().enumerate() {
-        info!("Primitive {}: {}", i, primitive.to_string());
+        info!("Primitive {} ({}): {}", i, primitive.name(), primitive.to_string());
     }
 
     // Save result
-    let output_path = args.output.as_ref().map(|s| s.as_str()).unwrap_or("result/output_all_rings.png");
+    let output_path = args.output.as_deref().unwrap_or(DEFAULT_OUTPUT);
     if let Err(e) = image.save(output_path) {
         error!("Failed to save image: {:?}", e);
     } else {
@@ -102,7 +915,7 @@ fn main() -> Result<(), ElsdcError> {
     }
 
     let matrix_output = args.output.as_ref()
-        .map(|s| s.replace(".png", "_matrix.txt"))
+        .map(|s| derived_path(s, "_matrix.txt"))
         .unwrap_or_else(|| "result/compatibility_matrix.txt".to_string());
     if let Err(e) = save_matrix_to_file(&compatibility_matrix, &matrix_output) {
         error!("Failed to save compatibility matrix: {:?}", e);
@@ -112,3 +925,21 @@ fn main() -> Result<(), ElsdcError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_paths() {
+        assert_eq!(derived_path("out/a.png", "_matrix.txt"), "out/a_matrix.txt");
+        // 非 .png 输出不会与自身或彼此重名
+        let output = "out/scene.jpg";
+        let results = derived_path(output, "_detections.json");
+        let matrix = derived_path(output, "_matrix.txt");
+        assert_eq!(results, "out/scene_detections.json");
+        assert_eq!(matrix, "out/scene_matrix.txt");
+        assert!(results != output && matrix != output);
+        assert_eq!(derived_path("a.b.tiff", "_matrix.txt"), "a.b_matrix.txt");
+    }
+}
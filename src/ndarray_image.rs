@@ -0,0 +1,120 @@
+/**
+ * File: /src/ndarray_image.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 10:41:15 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use ndarray::{ArrayBase, ArrayView2, Data, Ix2};
+
+use crate::elsdc::{Detection, Detector};
+use crate::gray_image::GrayImage;
+use crate::primitives::Image;
+use crate::ElsdcError;
+
+/// Height and width of a `[row, column]` array as image dimensions.
+fn dimensions<S: Data>(array: &ArrayBase<S, Ix2>) -> Result<(u32, u32), ElsdcError> {
+    let (rows, cols) = array.dim();
+    let width = u32::try_from(cols)
+        .map_err(|_| ElsdcError::ImageConversionError("Array width out of range".to_string()))?;
+    let height = u32::try_from(rows)
+        .map_err(|_| ElsdcError::ImageConversionError("Array height out of range".to_string()))?;
+    Ok((width, height))
+}
+
+impl Detector {
+    /// Detects on a 2-D array indexed `[y, x]`.
+    ///
+    /// Arrays in standard (row-major, contiguous) layout are handed to ELSDc
+    /// without copying; any other layout is copied once into a temporary.
+    pub fn detect_array<S: Data<Elem = f64>>(&self, array: &ArrayBase<S, Ix2>) -> Result<Detection, ElsdcError> {
+        let (width, height) = dimensions(array)?;
+        let pixels = array.as_standard_layout();
+        let data = pixels
+            .as_slice()
+            .expect("standard layout arrays are contiguous");
+        self.detect_slice(data, width, height)
+    }
+}
+
+impl GrayImage {
+    /// Borrows the pixels as an array indexed `[y, x]`.
+    pub fn view(&self) -> ArrayView2<'_, f64> {
+        ArrayView2::from_shape((self.height() as usize, self.width() as usize), self.data())
+            .expect("pixel count matches the image size")
+    }
+}
+
+impl TryFrom<ArrayView2<'_, f64>> for GrayImage {
+    type Error = ElsdcError;
+
+    fn try_from(array: ArrayView2<'_, f64>) -> Result<Self, Self::Error> {
+        let (width, height) = dimensions(&array)?;
+        GrayImage::from_vec(width, height, array.iter().copied().collect())
+    }
+}
+
+impl TryFrom<ArrayView2<'_, u8>> for GrayImage {
+    type Error = ElsdcError;
+
+    fn try_from(array: ArrayView2<'_, u8>) -> Result<Self, Self::Error> {
+        let (width, height) = dimensions(&array)?;
+        GrayImage::from_vec(width, height, array.iter().map(|&v| v as f64).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array2, ShapeBuilder};
+
+    #[test]
+    fn test_view_matches_pixels() {
+        let image = GrayImage::from_vec(3, 2, (0..6).map(|v| v as f64).collect()).unwrap();
+        let view = image.view();
+        assert_eq!(view.dim(), (2, 3));
+        assert_eq!(view[[1, 2]], image.get_pixel(2, 1).unwrap());
+    }
+
+    #[test]
+    fn test_from_views() {
+        let bytes = Array2::from_shape_fn((2, 3), |(y, x)| (y * 3 + x) as u8);
+        let gray = GrayImage::try_from(bytes.view()).unwrap();
+        assert_eq!((gray.width(), gray.height()), (3, 2));
+        assert_eq!(gray.get_pixel(1, 1).unwrap(), 4.0);
+
+        // 列优先的数组按逻辑顺序复制
+        let fortran = Array2::from_shape_vec((2, 3).f(), vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]).unwrap();
+        let gray = GrayImage::try_from(fortran.view()).unwrap();
+        assert_eq!(gray.data(), &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn test_detect_array_layouts() {
+        let array = Array2::from_shape_fn((60, 80), |(y, x)| {
+            let (dx, dy) = ((x as f64 - 40.0) / 20.0, (y as f64 - 30.0) / 15.0);
+            if dx * dx + dy * dy < 1.0 { 255.0 } else { 0.0 }
+        });
+        let detector = Detector::new();
+        let contiguous = detector.detect_array(&array).unwrap();
+        assert!(!contiguous.rings.is_empty(), "No ring detected in the test image");
+        assert_eq!((contiguous.labels.width, contiguous.labels.height), (80, 60));
+
+        // 转置后再转回：非标准布局会被复制，但结果一致
+        let transposed = array.t().to_owned();
+        let strided = detector.detect_array(&transposed.t()).unwrap();
+        assert_eq!(strided.rings.len(), contiguous.rings.len());
+        assert_eq!(strided.labels, contiguous.labels);
+    }
+
+    #[test]
+    fn test_detect_slice_checks_length() {
+        assert!(Detector::new().detect_slice(&[0.0; 5], 3, 2).is_err());
+    }
+}
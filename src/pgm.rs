@@ -11,13 +11,21 @@
  * ----------		------	---------------------------------------------------------
 **/
 
-use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader, Read};
-use std::path::Path;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+#[cfg(any(feature = "opencv", feature = "image"))]
+use std::{fs, path::Path};
 
+#[cfg(feature = "opencv")]
 use opencv::core::Vector;
+#[cfg(feature = "opencv")]
 use opencv::imgcodecs::{self, IMREAD_GRAYSCALE};
 
+#[cfg(any(feature = "opencv", feature = "image"))]
+use crate::color::GrayConversion;
+#[cfg(any(feature = "opencv", feature = "image"))]
+use crate::gray_image::GrayImage;
+#[cfg(any(feature = "opencv", feature = "image"))]
 use crate::ElsdcError;
 
 #[derive(Debug)]
@@ -27,95 +35,433 @@ pub struct PImageDouble {
     pub data: Vec<f64>,
 }
 
-pub fn read_pgm_image_double_rust(filename: &str) -> io::Result<PImageDouble> {
-    let file = File::open(filename)?;
-    let mut reader = BufReader::new(file);
+/// A decoded Netpbm image (PBM, PGM, PPM or PAM) with its raw samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetpbmImage {
+    pub width: usize,
+    pub height: usize,
+    /// Samples per pixel: 1 for PBM/PGM, 3 for PPM, `DEPTH` for PAM.
+    pub depth: usize,
+    /// Largest sample value; 1 for bitmaps, where 1 means white.
+    pub maxval: u16,
+    /// `TUPLTYPE` of a PAM file.
+    pub tuple_type: Option<String>,
+    /// Interleaved samples in row-major order.
+    pub samples: Vec<u16>,
+}
 
-    let mut magic_number = String::new();
-    reader.read_line(&mut magic_number)?;
-    if !magic_number.starts_with("P2") && !magic_number.starts_with("P5") {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a PGM file"));
+impl NetpbmImage {
+    /// Grayscale copy scaled to `0..=255`.
+    ///
+    /// Colour images use the ITU-R BT.601 luma weights (as OpenCV does) and
+    /// alpha channels are ignored.
+    pub fn to_gray(&self) -> PImageDouble {
+        let scale = 255.0 / self.maxval as f64;
+        let data = self
+            .samples
+            .chunks_exact(self.depth)
+            .map(|px| {
+                let value = if self.depth >= 3 {
+                    0.299 * px[0] as f64 + 0.587 * px[1] as f64 + 0.114 * px[2] as f64
+                } else {
+                    px[0] as f64
+                };
+                value * scale
+            })
+            .collect();
+        PImageDouble { xsize: self.width, ysize: self.height, data }
     }
-    let bin = magic_number.starts_with("P5");
+}
 
-    let (xsize, ysize, depth) = read_pgm_header(&mut reader)?;
+/// Sample encoding of a written Netpbm file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// ASCII samples (`P2`).
+    Plain,
+    /// Binary samples (`P5`), big-endian when `maxval > 255`.
+    #[default]
+    Raw,
+}
 
-    if depth == 0 {
-        log::warn!("Warning: depth=0, probably invalid PGM file.");
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Splits a Netpbm header (and plain raster) into whitespace-separated tokens,
+/// skipping `#` comments wherever they appear.
+struct Tokenizer<R> {
+    reader: R,
+}
+
+impl<R: BufRead> Tokenizer<R> {
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        match self.reader.read(&mut buf)? {
+            0 => Ok(None),
+            _ => Ok(Some(buf[0])),
+        }
     }
 
-    let mut data = vec![0.0; xsize * ysize];
+    /// Next non-whitespace byte outside comments.
+    fn significant_byte(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match self.byte()? {
+                Some(b'#') => {
+                    let mut comment = Vec::new();
+                    self.reader.read_until(b'\n', &mut comment)?;
+                }
+                Some(b) if b.is_ascii_whitespace() => {}
+                other => return Ok(other),
+            }
+        }
+    }
 
-    if bin {
-        let mut buffer = vec![0u8; xsize * ysize];
-        reader.read_exact(&mut buffer)?;
-        for (i, &value) in buffer.iter().enumerate() {
-            data[i] = value as f64;
+    /// Next token; the single whitespace byte that ends it is consumed too,
+    /// which is exactly the separator before a binary raster.
+    fn token(&mut self) -> io::Result<String> {
+        let first = self
+            .significant_byte()?
+            .ok_or_else(|| invalid("Unexpected end of file in header"))?;
+        let mut token = vec![first];
+        while let Some(b) = self.byte()? {
+            if b.is_ascii_whitespace() {
+                break;
+            }
+            if b == b'#' {
+                let mut comment = Vec::new();
+                self.reader.read_until(b'\n', &mut comment)?;
+                break;
+            }
+            token.push(b);
         }
+        String::from_utf8(token).map_err(|_| invalid("Header is not ASCII"))
+    }
+
+    fn number(&mut self, what: &str) -> io::Result<usize> {
+        let token = self.token()?;
+        token
+            .parse::<usize>()
+            .map_err(|e| invalid(format!("Invalid {} {:?}: {}", what, token, e)))
+    }
+}
+
+fn check_maxval(maxval: usize) -> io::Result<u16> {
+    match u16::try_from(maxval) {
+        Ok(v) if v > 0 => Ok(v),
+        _ => Err(invalid(format!("Unsupported maxval {}", maxval))),
+    }
+}
+
+/// Largest buffer reserved before the pixel data has actually been read, so
+/// that a header announcing a huge image fails on the missing data instead
+/// of aborting on allocation.
+const MAX_PREALLOCATION: usize = 1 << 24;
+
+fn sample_count(width: usize, height: usize, depth: usize) -> io::Result<usize> {
+    width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(depth))
+        .ok_or_else(|| invalid("Image dimensions overflow"))
+}
+
+/// Reads big-endian samples of one or two bytes each.
+fn read_raw_samples<R: Read>(reader: &mut R, count: usize, maxval: u16) -> io::Result<Vec<u16>> {
+    let wide = maxval > 255;
+    let len = count
+        .checked_mul(if wide { 2 } else { 1 })
+        .ok_or_else(|| invalid("Image dimensions overflow"))?;
+    let mut bytes = Vec::with_capacity(len.min(MAX_PREALLOCATION));
+    reader.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("Expected {} bytes of pixel data, found {}", len, bytes.len()),
+        ));
+    }
+    let samples: Vec<u16> = if wide {
+        bytes.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect()
     } else {
-        for value in &mut data {
-            let mut pixel_value = String::new();
-            reader.read_line(&mut pixel_value)?;
-            *value = pixel_value.trim().parse::<f64>().map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Invalid pixel value: {}", e),
-                )
-            })?;
-        }
+        bytes.into_iter().map(u16::from).collect()
+    };
+    if let Some(v) = samples.iter().find(|&&v| v > maxval) {
+        return Err(invalid(format!("Sample {} exceeds maxval {}", v, maxval)));
     }
+    Ok(samples)
+}
 
-    Ok(PImageDouble { xsize, ysize, data })
+fn read_plain_samples<R: BufRead>(tokens: &mut Tokenizer<R>, count: usize, maxval: u16) -> io::Result<Vec<u16>> {
+    (0..count)
+        .map(|_| {
+            let v = tokens.number("sample")?;
+            u16::try_from(v)
+                .ok()
+                .filter(|&v| v <= maxval)
+                .ok_or_else(|| invalid(format!("Sample {} exceeds maxval {}", v, maxval)))
+        })
+        .collect()
 }
 
-pub fn read_pgm_header<R: BufRead>(reader: &mut R) -> io::Result<(usize, usize, usize)> {
-    let mut width = String::new();
-    let mut height = String::new();
-    let mut depth = String::new();
+/// PBM stores 1 for black; samples are flipped so that 1 means white.
+fn read_plain_bits<R: BufRead>(tokens: &mut Tokenizer<R>, count: usize) -> io::Result<Vec<u16>> {
+    (0..count)
+        .map(|_| match tokens.significant_byte()? {
+            Some(b'0') => Ok(1),
+            Some(b'1') => Ok(0),
+            Some(b) => Err(invalid(format!("Invalid bit {:?}", b as char))),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+        })
+        .collect()
+}
+
+fn read_raw_bits<R: Read>(reader: &mut R, width: usize, height: usize) -> io::Result<Vec<u16>> {
+    let row_bytes = width.div_ceil(8);
+    let mut row = vec![0u8; row_bytes];
+    let mut samples = Vec::with_capacity(sample_count(width, height, 1)?.min(MAX_PREALLOCATION));
+    for _ in 0..height {
+        reader.read_exact(&mut row)?;
+        samples.extend((0..width).map(|x| u16::from(row[x / 8] & (0x80 >> (x % 8)) == 0)));
+    }
+    Ok(samples)
+}
 
+fn read_pam_header<R: BufRead>(tokens: &mut Tokenizer<R>) -> io::Result<(usize, usize, usize, u16, Option<String>)> {
+    let (mut width, mut height, mut depth, mut maxval, mut tuple_type) = (None, None, None, None, None);
     loop {
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-        if line.starts_with('#') {
-            continue;
+        let key = tokens.token()?;
+        match key.as_str() {
+            "WIDTH" => width = Some(tokens.number("width")?),
+            "HEIGHT" => height = Some(tokens.number("height")?),
+            "DEPTH" => depth = Some(tokens.number("depth")?),
+            "MAXVAL" => maxval = Some(check_maxval(tokens.number("maxval")?)?),
+            "TUPLTYPE" => tuple_type = Some(tokens.token()?),
+            "ENDHDR" => break,
+            other => return Err(invalid(format!("Unknown PAM header field {:?}", other))),
         }
+    }
+    let missing = |field: &str| invalid(format!("PAM header lacks {}", field));
+    let depth = depth.ok_or_else(|| missing("DEPTH"))?;
+    if depth == 0 {
+        return Err(invalid("PAM depth must be positive"));
+    }
+    Ok((
+        width.ok_or_else(|| missing("WIDTH"))?,
+        height.ok_or_else(|| missing("HEIGHT"))?,
+        depth,
+        maxval.ok_or_else(|| missing("MAXVAL"))?,
+        tuple_type,
+    ))
+}
 
-        let mut parts = line.split_whitespace();
-        if width.is_empty() {
-            if let Some(part) = parts.next() {
-                width.push_str(part);
-            }
+/// Reads any Netpbm image: `P1`–`P6` and PAM (`P7`), 8- or 16-bit.
+pub fn read_netpbm<R: BufRead>(reader: R) -> io::Result<NetpbmImage> {
+    let mut tokens = Tokenizer { reader };
+    let Header { magic, width, height, depth, maxval, tuple_type } = read_header(&mut tokens)?;
+
+    let count = sample_count(width, height, depth)?;
+    let samples = match magic.as_str() {
+        "P1" => read_plain_bits(&mut tokens, count)?,
+        "P4" => read_raw_bits(&mut tokens.reader, width, height)?,
+        "P2" | "P3" => read_plain_samples(&mut tokens, count, maxval)?,
+        _ => read_raw_samples(&mut tokens.reader, count, maxval)?,
+    };
+
+    Ok(NetpbmImage { width, height, depth, maxval, tuple_type, samples })
+}
+
+struct Header {
+    magic: String,
+    width: usize,
+    height: usize,
+    depth: usize,
+    maxval: u16,
+    tuple_type: Option<String>,
+}
+
+fn read_header<R: BufRead>(tokens: &mut Tokenizer<R>) -> io::Result<Header> {
+    let magic = tokens.token()?;
+    let (width, height, depth, maxval, tuple_type) = match magic.as_str() {
+        "P1" | "P4" => (tokens.number("width")?, tokens.number("height")?, 1, 1, None),
+        "P2" | "P3" | "P5" | "P6" => {
+            let (width, height) = (tokens.number("width")?, tokens.number("height")?);
+            let maxval = check_maxval(tokens.number("maxval")?)?;
+            let depth = if magic == "P3" || magic == "P6" { 3 } else { 1 };
+            (width, height, depth, maxval, None)
         }
-        if height.is_empty() {
-            if let Some(part) = parts.next() {
-                height.push_str(part);
-            }
+        "P7" => read_pam_header(tokens)?,
+        _ => return Err(invalid(format!("Not a Netpbm file (magic {:?})", magic))),
+    };
+    Ok(Header { magic, width, height, depth, maxval, tuple_type })
+}
+
+/// A binary Netpbm file (`P5`, `P6` or `P7`) whose pixels are read on demand,
+/// so only the requested rectangle is ever held in memory.
+#[derive(Debug)]
+pub struct NetpbmRows {
+    reader: BufReader<File>,
+    width: usize,
+    height: usize,
+    depth: usize,
+    maxval: u16,
+    /// File offset of the first sample.
+    data_start: u64,
+}
+
+impl NetpbmRows {
+    /// Reads the header of `path`; plain (ASCII) and bitmap files are rejected
+    /// because their rows cannot be located without parsing everything before.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = read_header(&mut Tokenizer { reader: &mut reader })?;
+        if !matches!(header.magic.as_str(), "P5" | "P6" | "P7") {
+            return Err(invalid(format!("{} files cannot be read row by row", header.magic)));
         }
-        if depth.is_empty() {
-            if let Some(part) = parts.next() {
-                depth.push_str(part);
-            }
+        let bytes_per_sample = if header.maxval > 255 { 2 } else { 1 };
+        let data_len = sample_count(header.width, header.height, header.depth)?
+            .checked_mul(bytes_per_sample)
+            .ok_or_else(|| invalid("Image dimensions overflow"))?;
+        let data_start = reader.stream_position()?;
+        let available = reader.get_ref().metadata()?.len().saturating_sub(data_start);
+        if (data_len as u64) > available {
+            return Err(invalid(format!(
+                "Header announces {} bytes of pixel data but only {} follow",
+                data_len, available
+            )));
         }
-        if !width.is_empty() && !height.is_empty() && !depth.is_empty() {
-            break;
+        Ok(Self {
+            reader,
+            width: header.width,
+            height: header.height,
+            depth: header.depth,
+            maxval: header.maxval,
+            data_start,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Grayscale pixels of the `width x height` rectangle at `(x, y)`, scaled
+    /// like [`NetpbmImage::to_gray`].
+    pub fn read_gray(&mut self, x: usize, y: usize, width: usize, height: usize) -> io::Result<PImageDouble> {
+        if x.checked_add(width).is_none_or(|end| end > self.width)
+            || y.checked_add(height).is_none_or(|end| end > self.height)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Rectangle {}x{} at ({}, {}) exceeds the {}x{} image",
+                    width, height, x, y, self.width, self.height
+                ),
+            ));
+        }
+        let bytes_per_sample = if self.maxval > 255 { 2 } else { 1 };
+        let pixel_bytes = (self.depth * bytes_per_sample) as u64;
+        let mut samples = Vec::with_capacity(width * height * self.depth);
+        for row in y..y + height {
+            let offset = self.data_start + (row as u64 * self.width as u64 + x as u64) * pixel_bytes;
+            self.reader.seek(SeekFrom::Start(offset))?;
+            samples.extend(read_raw_samples(&mut self.reader, width * self.depth, self.maxval)?);
         }
+        let tile = NetpbmImage { width, height, depth: self.depth, maxval: self.maxval, tuple_type: None, samples };
+        Ok(tile.to_gray())
     }
+}
 
-    let width = width
-        .trim()
-        .parse::<usize>()
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid width: {}", e)))?;
-    let height = height.trim().parse::<usize>().map_err(|e| {
-        io::Error::new(io::ErrorKind::InvalidData, format!("Invalid height: {}", e))
-    })?;
-    let depth = depth
-        .trim()
-        .parse::<usize>()
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid depth: {}", e)))?;
+/// Reads a Netpbm file as a grayscale image scaled to `0..=255`.
+///
+/// Despite the name every Netpbm variant is accepted; see [`read_netpbm`].
+pub fn read_pgm_image_double_rust(filename: &str) -> io::Result<PImageDouble> {
+    let file = File::open(filename)?;
+    Ok(read_netpbm(BufReader::new(file))?.to_gray())
+}
 
+/// Reads the three numbers following the magic number of a PGM/PPM header as
+/// width, height and depth.
+pub fn read_pgm_header<R: BufRead>(reader: &mut R) -> io::Result<(usize, usize, usize)> {
+    let mut tokens = Tokenizer { reader };
+    let width = tokens.number("width")?;
+    let height = tokens.number("height")?;
+    let depth = tokens.number("depth")?;
     Ok((width, height, depth))
 }
 
+/// Whether `path` has a Netpbm extension (pbm, pgm, ppm, pnm or pam).
+#[cfg(feature = "image")]
+pub(crate) fn is_netpbm_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        ["pbm", "pgm", "ppm", "pnm", "pam"].iter().any(|e| ext.eq_ignore_ascii_case(e))
+    })
+}
+
+/// Writes `image` as a PGM whose samples span `0..=maxval`.
+///
+/// Pixel values are taken to be in `0..=255` (as returned by the reader) and
+/// rescaled to `maxval`, then rounded and clamped.
+pub fn write_pgm<W: Write>(writer: &mut W, image: &PImageDouble, maxval: u16, encoding: Encoding) -> io::Result<()> {
+    write_gray(writer, &image.data, image.xsize, image.ysize, maxval, encoding)
+}
+
+/// Writes row-major gray values in `0..=255` as a PGM; see [`write_pgm`].
+pub(crate) fn write_gray<W: Write>(
+    writer: &mut W,
+    data: &[f64],
+    width: usize,
+    height: usize,
+    maxval: u16,
+    encoding: Encoding,
+) -> io::Result<()> {
+    if maxval == 0 {
+        return Err(invalid("maxval must be positive"));
+    }
+    if data.len() != width * height {
+        return Err(invalid("Pixel count does not match the image size"));
+    }
+    let scale = maxval as f64 / 255.0;
+    let samples = data.iter().map(|v| (v * scale).round().clamp(0.0, maxval as f64) as u16);
+
+    match encoding {
+        Encoding::Raw => {
+            write!(writer, "P5\n{} {}\n{}\n", width, height, maxval)?;
+            let bytes: Vec<u8> = if maxval > 255 {
+                samples.flat_map(u16::to_be_bytes).collect()
+            } else {
+                samples.map(|v| v as u8).collect()
+            };
+            writer.write_all(&bytes)?;
+        }
+        Encoding::Plain => {
+            write!(writer, "P2\n{} {}\n{}\n", width, height, maxval)?;
+            let samples: Vec<u16> = samples.collect();
+            for row in samples.chunks(width.max(1)) {
+                let line: Vec<String> = row.iter().map(u16::to_string).collect();
+                writeln!(writer, "{}", line.join(" "))?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// Writes interleaved RGB bytes as an 8-bit binary PPM.
+pub(crate) fn write_ppm<W: Write>(writer: &mut W, rgb: &[u8], width: usize, height: usize) -> io::Result<()> {
+    if rgb.len() != width * height * 3 {
+        return Err(invalid("Pixel count does not match the image size"));
+    }
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    writer.write_all(rgb)?;
+    writer.flush()
+}
+
+/// Writes `image` to `filename` as an 8-bit binary PGM.
+pub fn write_pgm_image_double_rust(filename: &str, image: &PImageDouble) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write_pgm(&mut writer, image, 255, Encoding::Raw)
+}
+
 pub fn scale_data(data: &mut [f64], max_value: f64) {
     let min = *data
         .iter()
@@ -130,26 +476,171 @@ pub fn scale_data(data: &mut [f64], max_value: f64) {
     }
 }
 
+/// Writes a grayscale PGM copy of `filename` into `pgm/` and returns its path.
+///
+/// Only needed to feed external tools; detection decodes images in memory.
+/// Decoding uses OpenCV when available, otherwise the `image` crate.
+#[cfg(any(feature = "opencv", feature = "image"))]
 pub fn ensure_pgm_image(filename: &str) -> Result<String, ElsdcError> {
     if filename.to_lowercase().ends_with(".pgm") {
         return Ok(filename.to_string());
     }
 
-    let img =
-        imgcodecs::imread(filename, IMREAD_GRAYSCALE).map_err(ElsdcError::OpenCVError)?;
+    let new_filename = pgm_copy_path(filename)?;
+    fs::create_dir_all("pgm")?;
+
+    #[cfg(feature = "opencv")]
+    {
+        let img =
+            imgcodecs::imread(filename, IMREAD_GRAYSCALE).map_err(ElsdcError::OpenCVError)?;
+        imgcodecs::imwrite(&new_filename, &img, &Vector::new())
+            .map_err(ElsdcError::OpenCVError)?;
+    }
+    #[cfg(not(feature = "opencv"))]
+    GrayImage::open(filename)?.save(&new_filename)?;
+
+    Ok(new_filename)
+}
+
+/// Like [`ensure_pgm_image`], but reads the image in colour and reduces it
+/// with `conversion`; PGM inputs are rewritten too.
+#[cfg(any(feature = "opencv", feature = "image"))]
+pub fn ensure_pgm_image_with(filename: &str, conversion: GrayConversion) -> Result<String, ElsdcError> {
+    let new_filename = pgm_copy_path(filename)?;
+    GrayImage::open_with(filename, conversion)?.save(&new_filename)?;
+    Ok(new_filename)
+}
 
-    let new_filename = format!(
+#[cfg(any(feature = "opencv", feature = "image"))]
+fn pgm_copy_path(filename: &str) -> Result<String, ElsdcError> {
+    Ok(format!(
         "pgm/{}.pgm",
         Path::new(filename)
             .file_stem()
             .ok_or_else(|| ElsdcError::DetectionError("Invalid filename".to_string()))?
             .to_string_lossy()
-    );
+    ))
+}
 
-    fs::create_dir_all("pgm")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    imgcodecs::imwrite(&new_filename, &img, &Vector::new())
-        .map_err(ElsdcError::OpenCVError)?;
+    fn read(bytes: &[u8]) -> io::Result<NetpbmImage> {
+        read_netpbm(bytes)
+    }
 
-    Ok(new_filename)
+    #[test]
+    fn test_plain_pgm_with_comments() {
+        let image = read(b"P2 # comment right after the magic\n3 2\n# maxval next\n10\n0 5 10\n10 5 0\n").unwrap();
+        assert_eq!((image.width, image.height, image.depth, image.maxval), (3, 2, 1, 10));
+        assert_eq!(image.samples, vec![0, 5, 10, 10, 5, 0]);
+        assert_eq!(image.to_gray().data[1], 127.5);
+    }
+
+    #[test]
+    fn test_raw_pgm_16_bit() {
+        let mut bytes = b"P5\n2 1\n65535\n".to_vec();
+        bytes.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+        let gray = read(&bytes).unwrap().to_gray();
+        assert_eq!(gray.data, vec![0.0, 255.0]);
+    }
+
+    #[test]
+    fn test_raw_pgm_whitespace_byte_value() {
+        // 第一个像素值恰好是换行符的字节值
+        let mut bytes = b"P5 2 1 255\n".to_vec();
+        bytes.extend_from_slice(&[b'\n', 200]);
+        assert_eq!(read(&bytes).unwrap().samples, vec![10, 200]);
+    }
+
+    #[test]
+    fn test_bitmaps() {
+        let plain = read(b"P1\n3 2\n010\n1 0 1\n").unwrap();
+        assert_eq!(plain.samples, vec![1, 0, 1, 0, 1, 0]);
+
+        let mut raw = b"P4\n3 2\n".to_vec();
+        raw.extend_from_slice(&[0b0100_0000, 0b1010_0000]);
+        assert_eq!(read(&raw).unwrap().samples, plain.samples);
+        assert_eq!(plain.to_gray().data[0], 255.0);
+    }
+
+    #[test]
+    fn test_pixmaps() {
+        let plain = read(b"P3\n1 1\n255\n255 0 0\n").unwrap();
+        let mut raw = b"P6\n1 1\n255\n".to_vec();
+        raw.extend_from_slice(&[255, 0, 0]);
+        assert_eq!(read(&raw).unwrap(), plain);
+        assert!((plain.to_gray().data[0] - 0.299 * 255.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pam() {
+        let mut bytes = b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\n# alpha\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n".to_vec();
+        bytes.extend_from_slice(&[10, 255, 20, 0]);
+        let image = read(&bytes).unwrap();
+        assert_eq!(image.tuple_type.as_deref(), Some("GRAYSCALE_ALPHA"));
+        assert_eq!(image.to_gray().data, vec![10.0, 20.0]);
+
+        assert!(read(b"P7\nWIDTH 2\nHEIGHT 1\nENDHDR\n").is_err());
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(read(b"P9\n1 1\n255\n").is_err());
+        assert!(read(b"P2\n2 1\n10\n3 11\n").is_err());
+        assert!(read(b"P5\n2 2\n255\n\x01").is_err());
+        assert!(read(b"P2\n1 1\n0\n0\n").is_err());
+        // 头部声明的尺寸远大于实际数据时应返回错误，而不是先分配内存
+        assert!(read(b"P5\n4000000000 4000000000\n65535\n\x00\x01").is_err());
+        assert!(read(b"P5\n100000 100000\n255\n\x00\x01").is_err());
+        assert!(read(b"P4\n100000 100000\n\x00").is_err());
+    }
+
+    #[test]
+    fn test_rows_read_rectangles() {
+        let path = std::env::temp_dir().join("elsdc_pgm_rows_test.pgm");
+        let data: Vec<f64> = (0..20).map(|v| (v * 10) as f64).collect();
+        let image = PImageDouble { xsize: 5, ysize: 4, data };
+        for maxval in [255, 1000] {
+            let mut file = File::create(&path).unwrap();
+            write_pgm(&mut file, &image, maxval, Encoding::Raw).unwrap();
+            drop(file);
+
+            let mut rows = NetpbmRows::open(&path).unwrap();
+            assert_eq!((rows.width(), rows.height()), (5, 4));
+            let tile = rows.read_gray(1, 2, 3, 2).unwrap();
+            let expected = [110.0, 120.0, 130.0, 160.0, 170.0, 180.0];
+            for (a, b) in tile.data.iter().zip(expected) {
+                assert!((a - b).abs() < 0.2, "{} {}", a, b);
+            }
+            assert_eq!(rows.read_gray(3, 0, 3, 1).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+            // 溢出的坐标同样被拒绝，而不是回绕
+            assert!(rows.read_gray(usize::MAX, 0, 2, 1).is_err());
+            assert!(rows.read_gray(0, 1, 1, usize::MAX).is_err());
+        }
+
+        let mut file = File::create(&path).unwrap();
+        write_pgm(&mut file, &image, 255, Encoding::Plain).unwrap();
+        drop(file);
+        assert!(NetpbmRows::open(&path).is_err());
+
+        std::fs::write(&path, b"P5\n100000 100000\n255\n\x00\x01").unwrap();
+        assert!(NetpbmRows::open(&path).is_err());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let image = PImageDouble { xsize: 3, ysize: 2, data: vec![0.0, 12.5, 255.0, 100.0, 7.0, 300.0] };
+        for (maxval, encoding) in [(255, Encoding::Raw), (255, Encoding::Plain), (65535, Encoding::Raw)] {
+            let mut bytes = Vec::new();
+            write_pgm(&mut bytes, &image, maxval, encoding).unwrap();
+            let back = read(&bytes).unwrap().to_gray();
+            assert_eq!((back.xsize, back.ysize), (3, 2));
+            for (a, b) in back.data.iter().zip(&image.data) {
+                assert!((a - b.min(255.0)).abs() <= 0.5, "{:?} {} {}", encoding, a, b);
+            }
+        }
+    }
 }
@@ -0,0 +1,166 @@
+/**
+ * File: /src/polygon.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 10:12:40 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use libc::{c_double, c_int};
+#[cfg(feature = "opencv")]
+use opencv::core::{self, Point};
+#[cfg(feature = "opencv")]
+use opencv::imgproc;
+use serde::{Deserialize, Serialize};
+
+use crate::gray_image::GrayImage;
+#[cfg(feature = "opencv")]
+use crate::image_processing::OpenCVImage;
+use crate::primitives::{Image, Primitive};
+use crate::ElsdcError;
+
+/// A point with sub-pixel coordinates, identical to `PointD` on the C side.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PointD {
+    pub x: c_double,
+    pub y: c_double,
+}
+
+/// Polygon as returned by ELSDc: `dim` points stored in a `malloc`ed array.
+#[repr(C)]
+#[derive(Debug)]
+pub(crate) struct RawPolygon {
+    pub dim: c_int,
+    pub pts: *mut PointD,
+}
+
+/// A chain of line segments detected by ELSDc.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Polygon {
+    pub points: Vec<PointD>,
+}
+
+impl Polygon {
+    /// Copies the points of a C polygon into a Rust-owned `Polygon`.
+    ///
+    /// # Safety
+    ///
+    /// `raw.pts` must be null or point to at least `raw.dim` valid `PointD`s.
+    pub(crate) unsafe fn from_raw(raw: &RawPolygon) -> Self {
+        if raw.pts.is_null() || raw.dim <= 0 {
+            return Self::default();
+        }
+        let points = std::slice::from_raw_parts(raw.pts, raw.dim as usize).to_vec();
+        Self { points }
+    }
+
+    /// Number of line segments in the chain.
+    pub fn segment_count(&self) -> usize {
+        self.points.len().saturating_sub(1)
+    }
+
+    /// Iterates over the line segments as pairs of consecutive points.
+    pub fn segments(&self) -> impl Iterator<Item = (PointD, PointD)> + '_ {
+        self.points.windows(2).map(|w| (w[0], w[1]))
+    }
+
+    /// Total length of the chain.
+    pub fn length(&self) -> f64 {
+        self.segments()
+            .map(|(a, b)| ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt())
+            .sum()
+    }
+
+    /// The same chain with every coordinate multiplied by `factor`.
+    pub fn scaled(&self, factor: f64) -> Polygon {
+        let points = self.points.iter().map(|p| PointD { x: p.x * factor, y: p.y * factor }).collect();
+        Polygon { points }
+    }
+
+    /// The same chain shifted by `(dx, dy)`.
+    pub fn translated(&self, dx: f64, dy: f64) -> Polygon {
+        let points = self.points.iter().map(|p| PointD { x: p.x + dx, y: p.y + dy }).collect();
+        Polygon { points }
+    }
+}
+
+impl Primitive for Polygon {
+    fn draw(&self, image: &mut dyn Image) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(gray) = image.as_any_mut().downcast_mut::<GrayImage>() {
+            let points: Vec<(f64, f64)> = self.points.iter().map(|p| (p.x, p.y)).collect();
+            gray.draw_polyline(&points, 0.0, 1.0);
+            return Ok(());
+        }
+
+        #[cfg(feature = "opencv")]
+        if let Some(opencv_image) = image.as_any_mut().downcast_mut::<OpenCVImage>() {
+            return self.draw_opencv(opencv_image);
+        }
+
+        Err(Box::new(ElsdcError::ImageConversionError("Unsupported image type for drawing".to_string())))
+    }
+
+    fn to_string(&self) -> String {
+        let points: Vec<String> = self.points.iter().map(|p| format!("({}, {})", p.x, p.y)).collect();
+        format!("Polygon: {} points [{}]", self.points.len(), points.join(", "))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Polygon {
+    #[cfg(feature = "opencv")]
+    fn draw_opencv(&self, opencv_image: &mut OpenCVImage) -> Result<(), Box<dyn std::error::Error>> {
+        let color = core::Scalar::new(0.0, 0.0, 0.0, 0.0); // 黑色
+        for (a, b) in self.segments() {
+            imgproc::line(
+                &mut opencv_image.mat,
+                Point::new(a.x.round() as i32, a.y.round() as i32),
+                Point::new(b.x.round() as i32, b.y.round() as i32),
+                color,
+                1,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_raw_copies_points() {
+        let mut pts = vec![
+            PointD { x: 0.0, y: 0.0 },
+            PointD { x: 3.0, y: 4.0 },
+            PointD { x: 3.0, y: 10.0 },
+        ];
+        let raw = RawPolygon { dim: pts.len() as c_int, pts: pts.as_mut_ptr() };
+        let polygon = unsafe { Polygon::from_raw(&raw) };
+        drop(pts);
+
+        assert_eq!(polygon.points.len(), 3);
+        assert_eq!(polygon.segment_count(), 2);
+        assert!((polygon.length() - 11.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_from_raw_null() {
+        let raw = RawPolygon { dim: 5, pts: std::ptr::null_mut() };
+        let polygon = unsafe { Polygon::from_raw(&raw) };
+        assert!(polygon.points.is_empty());
+        assert_eq!(polygon.segment_count(), 0);
+    }
+}
@@ -14,6 +14,9 @@
 use libc::c_double;
 use std::any::Any;
 
+use crate::polygon::Polygon;
+use crate::ring::Ring;
+
 /// Represents a primitive shape that can be drawn on an image.
 pub trait Primitive: Any {
     fn draw(&self, image: &mut dyn Image) -> Result<(), Box<dyn std::error::Error>>;
@@ -22,7 +25,17 @@ pub trait Primitive: Any {
 }
 
 /// Represents an image that can be drawn on.
-pub trait Image {
+///
+/// # Safety
+///
+/// The detector hands [`Image::as_ptr`] straight to ELSDc. Implementors must
+/// return either a null pointer or a pointer to `width() * height()`
+/// initialised `f64`s, contiguous and in row-major order, that stays valid and
+/// unchanged for as long as `self` is borrowed. [`Image::as_mut_ptr`] must
+/// obey the same rules for writes while `self` is mutably borrowed. Images
+/// that cannot guarantee this layout return null, and callers fall back to
+/// [`Image::copy_pixels`].
+pub unsafe trait Image {
     fn width(&self) -> u32;
     fn height(&self) -> u32;
     fn set_pixel(&mut self, x: u32, y: u32, value: f64) -> Result<(), Box<dyn std::error::Error>>;
@@ -31,4 +44,135 @@ pub trait Image {
     fn as_mut_ptr(&mut self) -> *mut f64;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
-}
\ No newline at end of file
+
+    /// Copies the pixels into a row-major buffer, one `get_pixel` at a time.
+    fn copy_pixels(&self) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let mut data = Vec::with_capacity(self.width() as usize * self.height() as usize);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                data.push(self.get_pixel(x, y)?);
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// A detected primitive, by kind.
+///
+/// Rings are classified from the FFI fields: `full` separates closed curves
+/// from arcs, and semi-axes equal within [`DetectedPrimitive::CIRCLE_TOLERANCE`]
+/// separate circles from ellipses.
+#[derive(Clone, Debug)]
+pub enum DetectedPrimitive {
+    Circle(Ring),
+    Ellipse(Ring),
+    CircularArc(Ring),
+    EllipticalArc(Ring),
+    Polygon(Polygon),
+}
+
+impl DetectedPrimitive {
+    /// Largest difference of the semi-axes, relative to the larger one, for a
+    /// ring to count as a circle.
+    pub const CIRCLE_TOLERANCE: f64 = 1e-6;
+
+    /// Kebab-case name of the kind, e.g. `"circular-arc"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectedPrimitive::Circle(_) => "circle",
+            DetectedPrimitive::Ellipse(_) => "ellipse",
+            DetectedPrimitive::CircularArc(_) => "circular-arc",
+            DetectedPrimitive::EllipticalArc(_) => "elliptical-arc",
+            DetectedPrimitive::Polygon(_) => "polygon",
+        }
+    }
+
+    /// The ring of every variant but [`DetectedPrimitive::Polygon`].
+    pub fn ring(&self) -> Option<&Ring> {
+        match self {
+            DetectedPrimitive::Circle(ring)
+            | DetectedPrimitive::Ellipse(ring)
+            | DetectedPrimitive::CircularArc(ring)
+            | DetectedPrimitive::EllipticalArc(ring) => Some(ring),
+            DetectedPrimitive::Polygon(_) => None,
+        }
+    }
+
+    pub fn polygon(&self) -> Option<&Polygon> {
+        match self {
+            DetectedPrimitive::Polygon(polygon) => Some(polygon),
+            _ => None,
+        }
+    }
+
+    fn as_primitive(&self) -> &dyn Primitive {
+        match self {
+            DetectedPrimitive::Circle(ring)
+            | DetectedPrimitive::Ellipse(ring)
+            | DetectedPrimitive::CircularArc(ring)
+            | DetectedPrimitive::EllipticalArc(ring) => ring,
+            DetectedPrimitive::Polygon(polygon) => polygon,
+        }
+    }
+}
+
+impl From<Ring> for DetectedPrimitive {
+    fn from(ring: Ring) -> Self {
+        let circle = (ring.ax - ring.bx).abs() <= Self::CIRCLE_TOLERANCE * ring.ax.abs().max(ring.bx.abs());
+        match (ring.full != 0, circle) {
+            (true, true) => DetectedPrimitive::Circle(ring),
+            (true, false) => DetectedPrimitive::Ellipse(ring),
+            (false, true) => DetectedPrimitive::CircularArc(ring),
+            (false, false) => DetectedPrimitive::EllipticalArc(ring),
+        }
+    }
+}
+
+impl From<Polygon> for DetectedPrimitive {
+    fn from(polygon: Polygon) -> Self {
+        DetectedPrimitive::Polygon(polygon)
+    }
+}
+
+impl Primitive for DetectedPrimitive {
+    fn draw(&self, image: &mut dyn Image) -> Result<(), Box<dyn std::error::Error>> {
+        self.as_primitive().draw(image)
+    }
+
+    fn to_string(&self) -> String {
+        self.as_primitive().to_string()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polygon::PointD;
+
+    fn ring(ax: f64, bx: f64, full: i32) -> Ring {
+        let ring = Ring::ellipse(10.0, 10.0, ax, bx, 0.0);
+        if full != 0 { ring } else { ring.arc(0.0, 1.0) }
+    }
+
+    #[test]
+    fn test_classify_rings() {
+        let kinds: Vec<&str> = [ring(5.0, 5.0, 1), ring(5.0, 3.0, 1), ring(5.0, 5.0, 0), ring(5.0, 3.0, 0)]
+            .into_iter()
+            .map(|r| DetectedPrimitive::from(r).name())
+            .collect();
+        assert_eq!(kinds, ["circle", "ellipse", "circular-arc", "elliptical-arc"]);
+
+        // 拟合得到的半轴几乎不会完全相等
+        let nearly_round = ring(25.0, 25.0 * (1.0 + 1e-9), 1);
+        assert!(matches!(DetectedPrimitive::from(nearly_round), DetectedPrimitive::Circle(_)));
+        assert!(matches!(DetectedPrimitive::from(ring(25.0, 24.9, 1)), DetectedPrimitive::Ellipse(_)));
+
+        let polygon = DetectedPrimitive::from(Polygon { points: vec![PointD { x: 1.0, y: 2.0 }] });
+        assert!(polygon.ring().is_none() && polygon.polygon().is_some());
+        assert!(matches!(DetectedPrimitive::from(ring(5.0, 3.0, 0)).ring(), Some(r) if r.bx == 3.0));
+    }
+}
@@ -0,0 +1,231 @@
+/**
+ * File: /src/pyramid.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 1:32:40 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use crate::elsdc::Detector;
+use crate::polygon::Polygon;
+use crate::primitives::{DetectedPrimitive, Image};
+use crate::ring::nms::{self, Score};
+use crate::ring::Ring;
+use crate::roi;
+use crate::ElsdcError;
+
+/// Levels of pyramid detection and how their results are fused.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PyramidConfig {
+    /// Number of levels, the full-resolution image included.
+    pub levels: usize,
+    /// Downsampling factor between consecutive levels, greater than 1.
+    pub factor: f64,
+    /// Levels whose shorter side would be below this many pixels are skipped.
+    pub min_size: u32,
+    /// Rings overlapping a better one (from any level) by more than this IoU are dropped.
+    pub iou_threshold: f64,
+}
+
+impl Default for PyramidConfig {
+    fn default() -> Self {
+        Self { levels: 3, factor: 2.0, min_size: 32, iou_threshold: 0.5 }
+    }
+}
+
+impl PyramidConfig {
+    pub fn new(levels: usize, factor: f64) -> Self {
+        Self { levels, factor, ..Self::default() }
+    }
+
+    fn validate(&self) -> Result<(), ElsdcError> {
+        if self.levels == 0 {
+            return Err(ElsdcError::ConfigError("A pyramid needs at least one level".into()));
+        }
+        if !(self.factor.is_finite() && self.factor > 1.0) {
+            return Err(ElsdcError::ConfigError(format!(
+                "Pyramid factor must be greater than 1, got {}",
+                self.factor
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Result of [`Detector::detect_pyramid`], in full-resolution coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct PyramidDetection {
+    pub width: u32,
+    pub height: u32,
+    /// Rings surviving duplicate suppression across levels.
+    pub rings: Vec<Ring>,
+    /// Pyramid level each entry of `rings` was found at, 0 being full resolution.
+    pub ring_levels: Vec<usize>,
+    /// Polygons of the full-resolution level.
+    pub polygons: Vec<Polygon>,
+    /// Number of levels actually processed.
+    pub levels: usize,
+}
+
+impl PyramidDetection {
+    /// Returns the detected rings followed by the polygons, classified by kind.
+    pub fn primitives(&self) -> Vec<DetectedPrimitive> {
+        let rings = self.rings.iter().map(|ring| DetectedPrimitive::from(*ring));
+        let polygons = self.polygons.iter().map(|polygon| DetectedPrimitive::from(polygon.clone()));
+        rings.chain(polygons).collect()
+    }
+}
+
+impl Detector {
+    /// Detects at full resolution and on successively downsampled copies.
+    ///
+    /// Each level is an area average of the previous one, so pixel `x` of a
+    /// level downsampled by `s` covers `[x * s, (x + 1) * s)` of the input and
+    /// its rings are mapped back with `p * s + (s - 1) / 2`. Rings of all
+    /// levels are then fused with [`nms::nms`], longer arcs first.
+    pub fn detect_pyramid<I: Image + ?Sized>(
+        &self,
+        image: &I,
+        config: &PyramidConfig,
+    ) -> Result<PyramidDetection, ElsdcError> {
+        config.validate()?;
+        let (width, height) = (image.width(), image.height());
+        let base = self.detect(image)?;
+        let mut rings = base.rings;
+        let mut ring_levels = vec![0; rings.len()];
+        let mut levels = 1;
+
+        let mut scale = 1.0;
+        let mut previous: Option<(Vec<f64>, usize, usize)> = None;
+        for level in 1..config.levels {
+            let (data, w, h) = match &previous {
+                None => downsample(&roi::pixels(image)?, width as usize, height as usize, config.factor),
+                Some((data, w, h)) => downsample(data, *w, *h, config.factor),
+            };
+            if w.min(h) < config.min_size.max(1) as usize {
+                break;
+            }
+            scale *= config.factor;
+            let offset = (scale - 1.0) / 2.0;
+            let detection = self.detect_slice(&data, w as u32, h as u32)?;
+            ring_levels.extend(std::iter::repeat_n(level, detection.rings.len()));
+            rings.extend(detection.rings.iter().map(|ring| ring.scaled(scale).translated(offset, offset)));
+            levels += 1;
+            previous = Some((data, w, h));
+        }
+
+        let mut keep = nms::nms(&rings, Score::ArcLength, config.iou_threshold)?;
+        keep.sort_unstable();
+        Ok(PyramidDetection {
+            width,
+            height,
+            rings: keep.iter().map(|&i| rings[i]).collect(),
+            ring_levels: keep.iter().map(|&i| ring_levels[i]).collect(),
+            polygons: base.polygons,
+            levels,
+        })
+    }
+}
+
+/// Normalised weights of the input samples covered by each output sample
+/// when `n_in` samples are reduced by `factor` to `n_out`.
+fn axis_weights(n_in: usize, n_out: usize, factor: f64) -> Vec<Vec<(usize, f64)>> {
+    (0..n_out)
+        .map(|i| {
+            let (lo, hi) = (i as f64 * factor, ((i + 1) as f64 * factor).min(n_in as f64));
+            let taps: Vec<(usize, f64)> = (lo.floor() as usize..(hi.ceil() as usize).min(n_in))
+                .map(|j| (j, hi.min(j as f64 + 1.0) - lo.max(j as f64)))
+                .filter(|&(_, w)| w > 0.0)
+                .collect();
+            let total: f64 = taps.iter().map(|&(_, w)| w).sum();
+            taps.into_iter().map(|(j, w)| (j, w / total)).collect()
+        })
+        .collect()
+}
+
+/// Area-averaging reduction of a row-major image by `factor`.
+fn downsample(data: &[f64], width: usize, height: usize, factor: f64) -> (Vec<f64>, usize, usize) {
+    let w = (width as f64 / factor).floor() as usize;
+    let h = (height as f64 / factor).floor() as usize;
+    let (wx, wy) = (axis_weights(width, w, factor), axis_weights(height, h, factor));
+
+    let rows: Vec<f64> = data
+        .chunks_exact(width.max(1))
+        .flat_map(|row| wx.iter().map(move |taps| taps.iter().map(|&(j, wt)| row[j] * wt).sum::<f64>()))
+        .collect();
+    let out = wy
+        .iter()
+        .flat_map(|taps| {
+            let rows = &rows;
+            (0..w).map(move |x| taps.iter().map(|&(j, wt)| rows[j * w + x] * wt).sum::<f64>())
+        })
+        .collect();
+    (out, w, h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gray_image::GrayImage;
+
+    #[test]
+    fn test_downsample_by_two_averages_blocks() {
+        let data: Vec<f64> = (0..16).map(|v| v as f64).collect();
+        let (out, w, h) = downsample(&data, 4, 4, 2.0);
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(out, vec![2.5, 4.5, 10.5, 12.5]);
+
+        // 奇数尺寸时最后一列/行被舍弃
+        let (_, w, h) = downsample(&[0.0; 35], 7, 5, 2.0);
+        assert_eq!((w, h), (3, 2));
+    }
+
+    #[test]
+    fn test_downsample_fractional_factor_keeps_mean() {
+        let data: Vec<f64> = (0..36).map(|v| ((v * 7) % 11) as f64).collect();
+        let (out, w, h) = downsample(&data, 6, 6, 1.5);
+        assert_eq!((w, h), (4, 4));
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        assert!((mean(&out) - mean(&data)).abs() < 1e-9);
+
+        let (flat, _, _) = downsample(&[3.0; 36], 6, 6, 1.5);
+        assert!(flat.iter().all(|&v| (v - 3.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(PyramidConfig::new(0, 2.0).validate().is_err());
+        assert!(PyramidConfig::new(3, 1.0).validate().is_err());
+        assert!(PyramidConfig::new(3, f64::NAN).validate().is_err());
+        assert!(PyramidConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_detect_pyramid_levels() {
+        let (width, height) = (200u32, 160u32);
+        let data = (0..width * height)
+            .map(|i| {
+                let (dx, dy) = (((i % width) as f64 - 100.0) / 70.0, ((i / width) as f64 - 80.0) / 55.0);
+                if dx * dx + dy * dy < 1.0 { 255.0 } else { 0.0 }
+            })
+            .collect();
+        let image = GrayImage::from_vec(width, height, data).unwrap();
+        let detector = Detector::new();
+
+        // 200x160 -> 100x80 -> 50x40，第四层 25x20 小于 min_size
+        let pyramid = detector.detect_pyramid(&image, &PyramidConfig::new(5, 2.0)).unwrap();
+        assert_eq!(pyramid.levels, 3);
+        assert_eq!(pyramid.ring_levels.len(), pyramid.rings.len());
+        for ring in &pyramid.rings {
+            assert!((ring.cx - 100.0).abs() < 5.0 && (ring.cy - 80.0).abs() < 5.0, "{:?}", ring);
+        }
+        let primitives = pyramid.primitives();
+        assert_eq!(primitives.len(), pyramid.rings.len() + pyramid.polygons.len());
+        assert!(primitives[..pyramid.rings.len()].iter().all(|p| p.ring().is_some()));
+    }
+}
@@ -0,0 +1,337 @@
+/**
+ * File: /src/render.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 5:12:36 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::path::Path;
+
+use opencv::core::{Mat, Point, Scalar, Size, Vector};
+use opencv::prelude::*;
+use opencv::{imgcodecs, imgproc};
+
+use crate::image_processing::OpenCVImage;
+use crate::label_map::{hue_color, label_color};
+use crate::polygon::Polygon;
+use crate::primitives::DetectedPrimitive;
+use crate::ring::nms::Score;
+use crate::ring::Ring;
+use crate::ElsdcError;
+
+/// Fractional bits of the coordinates passed to OpenCV, for sub-pixel drawing.
+const SHIFT: i32 = 4;
+const ONE: f64 = (1 << SHIFT) as f64;
+
+/// How the colour of each primitive is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorBy {
+    /// A distinct colour per primitive.
+    #[default]
+    Index,
+    /// One colour per kind: circle, ellipse, circular arc, elliptical arc, polygon.
+    Type,
+    /// Rings from blue (smallest) to red (largest angular coverage); polygons in gray.
+    ArcCoverage,
+    /// Rings from blue (shortest) to red (longest arc); polygons in gray.
+    ArcLength,
+}
+
+/// What a [`Renderer`] draws and how.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderStyle {
+    pub color_by: ColorBy,
+    /// Line thickness in pixels.
+    pub thickness: i32,
+    /// Anti-aliased (`LINE_AA`) instead of 8-connected lines.
+    pub antialias: bool,
+    /// Cross at the centre of every ring.
+    pub centers: bool,
+    /// Semi-axes of every ring.
+    pub axes: bool,
+    /// Dots at the arc endpoints `(x1, y1)` and `(x2, y2)`.
+    pub endpoints: bool,
+    /// Index of every primitive next to it.
+    pub labels: bool,
+    pub font_scale: f64,
+}
+
+impl Default for RenderStyle {
+    fn default() -> Self {
+        Self {
+            color_by: ColorBy::Index,
+            thickness: 1,
+            antialias: true,
+            centers: false,
+            axes: false,
+            endpoints: false,
+            labels: false,
+            font_scale: 0.4,
+        }
+    }
+}
+
+/// Draws detections in colour over a BGR copy of the input image.
+///
+/// Unlike [`crate::Primitive::draw`], which draws black into the grayscale
+/// working image, the source is left untouched.
+pub struct Renderer {
+    canvas: Mat,
+    style: RenderStyle,
+}
+
+impl Renderer {
+    /// Starts from a grayscale image with values in `0..=255`.
+    pub fn new(image: &OpenCVImage, style: RenderStyle) -> Result<Self, ElsdcError> {
+        Self::from_mat(&image.mat, style)
+    }
+
+    /// Starts from a grayscale, BGR or BGRA `Mat` of any depth.
+    ///
+    /// Values are saturated to 8 bits unchanged, so 16-bit images should be
+    /// scaled first.
+    pub fn from_mat(mat: &Mat, style: RenderStyle) -> Result<Self, ElsdcError> {
+        if mat.empty() {
+            return Err(ElsdcError::ImageReadError("Empty image".into()));
+        }
+        let mut bytes = Mat::default();
+        mat.convert_to(&mut bytes, opencv::core::CV_8U, 1.0, 0.0)?;
+        let code = match bytes.channels() {
+            1 => imgproc::COLOR_GRAY2BGR,
+            3 => return Ok(Self { canvas: bytes, style }),
+            4 => imgproc::COLOR_BGRA2BGR,
+            n => {
+                return Err(ElsdcError::ImageConversionError(format!(
+                    "Unsupported number of channels: {}",
+                    n
+                )))
+            }
+        };
+        let mut canvas = Mat::default();
+        imgproc::cvt_color(&bytes, &mut canvas, code, 0)?;
+        Ok(Self { canvas, style })
+    }
+
+    /// Starts from the image file at `path`, keeping its colours.
+    pub fn open<P: AsRef<Path>>(path: P, style: RenderStyle) -> Result<Self, ElsdcError> {
+        let path = path.as_ref();
+        let mat = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+        if mat.empty() {
+            return Err(ElsdcError::ImageReadError(format!("Failed to read image: {}", path.display())));
+        }
+        Self::from_mat(&mat, style)
+    }
+
+    pub fn style(&self) -> &RenderStyle {
+        &self.style
+    }
+
+    /// The BGR canvas drawn so far.
+    pub fn mat(&self) -> &Mat {
+        &self.canvas
+    }
+
+    pub fn into_mat(self) -> Mat {
+        self.canvas
+    }
+
+    /// Draws `rings` and `polygons`, coloured according to the style.
+    ///
+    /// Polygons are numbered after the rings, as in the label map.
+    pub fn draw(&mut self, rings: &[Ring], polygons: &[Polygon]) -> Result<(), ElsdcError> {
+        let scores = match self.style.color_by {
+            ColorBy::ArcCoverage => Some(Score::ArcCoverage.values(rings)?),
+            ColorBy::ArcLength => Some(Score::ArcLength.values(rings)?),
+            ColorBy::Index | ColorBy::Type => None,
+        };
+        self.draw_scored(rings, scores.as_deref(), polygons)
+    }
+
+    /// Like [`Renderer::draw`], colouring rings by caller-supplied scores
+    /// (e.g. `-log10(NFA)`) whatever the style's [`ColorBy`].
+    pub fn draw_scored(
+        &mut self,
+        rings: &[Ring],
+        scores: Option<&[f64]>,
+        polygons: &[Polygon],
+    ) -> Result<(), ElsdcError> {
+        if let Some(scores) = scores.filter(|s| s.len() != rings.len()) {
+            return Err(ElsdcError::DetectionError(format!(
+                "Got {} scores for {} rings",
+                scores.len(),
+                rings.len()
+            )));
+        }
+        let normalised = scores.map(normalise);
+        for (i, ring) in rings.iter().enumerate() {
+            let color = match &normalised {
+                Some(values) => score_color(values[i]),
+                None => self.color_of(i, type_color(&DetectedPrimitive::from(*ring))),
+            };
+            self.draw_ring(i, ring, color)?;
+        }
+        for (i, polygon) in polygons.iter().enumerate() {
+            let index = rings.len() + i;
+            let color = match normalised {
+                Some(_) => [160, 160, 160],
+                None => self.color_of(index, POLYGON_COLOR),
+            };
+            self.draw_polygon(index, polygon, color)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the canvas; the format follows the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ElsdcError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if !imgcodecs::imwrite(&path.to_string_lossy(), &self.canvas, &Vector::new())? {
+            return Err(ElsdcError::ImageConversionError(format!("Failed to write {}", path.display())));
+        }
+        Ok(())
+    }
+
+    /// `type_color` under [`ColorBy::Type`], the index colour otherwise.
+    fn color_of(&self, index: usize, type_color: [u8; 3]) -> [u8; 3] {
+        match self.style.color_by {
+            ColorBy::Type => type_color,
+            _ => label_color(index as i32 + 1),
+        }
+    }
+
+    fn line_type(&self) -> i32 {
+        if self.style.antialias { imgproc::LINE_AA } else { imgproc::LINE_8 }
+    }
+
+    fn draw_ring(&mut self, index: usize, ring: &Ring, color: [u8; 3]) -> Result<(), ElsdcError> {
+        let (scalar, thickness, line_type) = (bgr(color), self.style.thickness.max(1), self.line_type());
+        let center = point((ring.cx, ring.cy));
+        let axes = Size::new((ring.ax * ONE).round() as i32, (ring.bx * ONE).round() as i32);
+        // OpenCV 会交换 start > end 的角度，所以用 start + sweep 表示终点
+        let start = if ring.full != 0 { 0.0 } else { ring.ang_start.to_degrees() };
+        let end = start + ring.angular_coverage().to_degrees();
+        let angle = ring.theta.to_degrees();
+        imgproc::ellipse(&mut self.canvas, center, axes, angle, start, end, scalar, thickness, line_type, SHIFT)?;
+
+        if self.style.axes {
+            let (sin_t, cos_t) = ring.theta.sin_cos();
+            for (dx, dy) in [(ring.ax * cos_t, ring.ax * sin_t), (-ring.bx * sin_t, ring.bx * cos_t)] {
+                let tip = point((ring.cx + dx, ring.cy + dy));
+                imgproc::line(&mut self.canvas, center, tip, scalar, 1, line_type, SHIFT)?;
+            }
+        }
+        if self.style.centers {
+            let arm = 3.0 + thickness as f64;
+            for (dx, dy) in [(arm, 0.0), (0.0, arm)] {
+                let (a, b) = (point((ring.cx - dx, ring.cy - dy)), point((ring.cx + dx, ring.cy + dy)));
+                imgproc::line(&mut self.canvas, a, b, scalar, 1, line_type, SHIFT)?;
+            }
+        }
+        if self.style.endpoints && ring.full == 0 {
+            let radius = ((1.5 + thickness as f64) * ONE).round() as i32;
+            for end in [(ring.x1, ring.y1), (ring.x2, ring.y2)] {
+                imgproc::circle(&mut self.canvas, point(end), radius, scalar, imgproc::FILLED, line_type, SHIFT)?;
+            }
+        }
+        if self.style.labels {
+            self.put_label(index, (ring.cx + 2.0, ring.cy - 2.0), scalar)?;
+        }
+        Ok(())
+    }
+
+    fn draw_polygon(&mut self, index: usize, polygon: &Polygon, color: [u8; 3]) -> Result<(), ElsdcError> {
+        let points: Vector<Point> = polygon.points.iter().map(|p| point((p.x, p.y))).collect();
+        if points.is_empty() {
+            return Ok(());
+        }
+        let contours = Vector::<Vector<Point>>::from_iter([points]);
+        let (scalar, thickness, line_type) = (bgr(color), self.style.thickness.max(1), self.line_type());
+        imgproc::polylines(&mut self.canvas, &contours, false, scalar, thickness, line_type, SHIFT)?;
+        if self.style.labels {
+            let first = &polygon.points[0];
+            self.put_label(index, (first.x + 2.0, first.y - 2.0), scalar)?;
+        }
+        Ok(())
+    }
+
+    fn put_label(&mut self, index: usize, (x, y): (f64, f64), color: Scalar) -> Result<(), ElsdcError> {
+        let line_type = self.line_type();
+        imgproc::put_text(
+            &mut self.canvas,
+            &index.to_string(),
+            Point::new(x.round() as i32, y.round() as i32),
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            self.style.font_scale,
+            color,
+            1,
+            line_type,
+            false,
+        )?;
+        Ok(())
+    }
+}
+
+/// RGB colour of polygons under [`ColorBy::Type`].
+const POLYGON_COLOR: [u8; 3] = [200, 0, 255];
+
+/// RGB colour of the primitive's kind, for [`ColorBy::Type`].
+fn type_color(primitive: &DetectedPrimitive) -> [u8; 3] {
+    match primitive {
+        DetectedPrimitive::Circle(_) => [0, 200, 0],
+        DetectedPrimitive::Ellipse(_) => [255, 64, 64],
+        DetectedPrimitive::CircularArc(_) => [0, 200, 255],
+        DetectedPrimitive::EllipticalArc(_) => [255, 160, 0],
+        DetectedPrimitive::Polygon(_) => POLYGON_COLOR,
+    }
+}
+
+/// Maps scores linearly onto `0..=1`; all equal scores map to 1.
+fn normalise(scores: &[f64]) -> Vec<f64> {
+    let min = scores.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    scores
+        .iter()
+        .map(|&s| if max > min { (s - min) / (max - min) } else { 1.0 })
+        .collect()
+}
+
+/// Blue for 0 through green to red for 1.
+fn score_color(value: f64) -> [u8; 3] {
+    hue_color(240.0 * (1.0 - value.clamp(0.0, 1.0)))
+}
+
+fn bgr([r, g, b]: [u8; 3]) -> Scalar {
+    Scalar::new(b as f64, g as f64, r as f64, 0.0)
+}
+
+fn point((x, y): (f64, f64)) -> Point {
+    Point::new((x * ONE).round() as i32, (y * ONE).round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise_and_score_colors() {
+        assert_eq!(normalise(&[2.0, 4.0, 3.0]), vec![0.0, 1.0, 0.5]);
+        assert_eq!(normalise(&[5.0, 5.0]), vec![1.0, 1.0]);
+        let (low, high) = (score_color(0.0), score_color(1.0));
+        assert!(low[2] > low[0], "low scores are blue: {:?}", low);
+        assert!(high[0] > high[2], "high scores are red: {:?}", high);
+    }
+
+    #[test]
+    fn test_sub_pixel_points() {
+        assert_eq!(point((1.5, 2.25)), Point::new(24, 36));
+        assert_eq!(bgr([1, 2, 3]).0, [3.0, 2.0, 1.0, 0.0]);
+    }
+}
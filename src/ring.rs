@@ -12,30 +12,136 @@
 **/
 
 use libc::{c_double, c_int};
-use log::error;
+#[cfg(feature = "opencv")]
 use opencv::core::{Point, Scalar, Size};
-use std::fs::{self, File};
+use std::f64::consts::PI;
+use std::fs::File;
 use std::io::Write;
+#[cfg(feature = "opencv")]
 use opencv::{core, imgproc, prelude::*};
+use serde::{Deserialize, Serialize};
+use crate::geometry::{self, Pt};
+use crate::gray_image::GrayImage;
 use crate::primitives::{Primitive, Image};
 use crate::ElsdcError;
+#[cfg(feature = "opencv")]
 use crate::image_processing::OpenCVImage;
-use rand::distributions::{Alphanumeric, Distribution};
-use rand::Rng;
+
+pub mod compatibility;
+pub mod nms;
+
+pub use compatibility::{RingIndex, SparseMatrix};
+
+/// Boundary samples per full turn used by the ring metrics.
+const BOUNDARY_SAMPLES: usize = 256;
+
+/// Axis-aligned bounding box in image coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    /// Box grown by `margin` on every side.
+    pub fn expand(&self, margin: f64) -> Self {
+        Self {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    /// `true` when the boxes overlap or touch.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min_x <= other.max_x
+            && other.min_x <= self.max_x
+            && self.min_y <= other.max_y
+            && other.min_y <= self.max_y
+    }
+
+    pub(crate) fn is_finite(&self) -> bool {
+        self.min_x.is_finite() && self.min_y.is_finite() && self.max_x.is_finite() && self.max_y.is_finite()
+    }
+}
+
+/// How [`Ring::iou_with`] measures the overlap of two rings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IouMethod {
+    /// Polygon clipping of finely sampled boundaries (areas within about 1e-4).
+    #[default]
+    Analytic,
+    /// Counts pixels of two OpenCV masks. Kept as a reference; inaccurate for
+    /// small or thin rings.
+    Raster,
+}
+
+/// Pairwise similarity used to build a compatibility matrix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimilarityMetric {
+    /// Region intersection over union, see [`Ring::iou_with`].
+    Iou(IouMethod),
+    /// Agreement of the curves themselves, see [`Ring::arc_overlap`].
+    ArcOverlap {
+        /// Distance (in pixels) within which a sample counts as lying on the other curve.
+        tolerance: f64,
+    },
+}
+
+impl Default for SimilarityMetric {
+    fn default() -> Self {
+        SimilarityMetric::Iou(IouMethod::Analytic)
+    }
+}
 
 impl Primitive for Ring {
     fn draw(&self, image: &mut dyn Image) -> Result<(), Box<dyn std::error::Error>> {
-        let opencv_image = image.as_any_mut().downcast_mut::<OpenCVImage>()
-            .ok_or_else(|| Box::new(ElsdcError::ImageConversionError("Failed to downcast Image to OpenCVImage".to_string())))?;
-    
+        // let thickness = (self.ax + self.bx)/100.0 as i32;
+        // 四舍五入
+        let thickness = ((self.ax + self.bx) / 100.0).round();
+
+        if let Some(gray) = image.as_any_mut().downcast_mut::<GrayImage>() {
+            gray.draw_polyline(&self.arc_samples(), 0.0, thickness);
+            return Ok(());
+        }
+
+        #[cfg(feature = "opencv")]
+        if let Some(opencv_image) = image.as_any_mut().downcast_mut::<OpenCVImage>() {
+            return self.draw_opencv(opencv_image, thickness as i32);
+        }
+
+        Err(Box::new(ElsdcError::ImageConversionError("Unsupported image type for drawing".to_string())))
+    }
+
+    fn to_string(&self) -> String {
+        format!("Ring: center=({}, {}), axes=({}, {}), angle={}", 
+                self.cx, self.cy, self.ax, self.bx, self.theta)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Ring {
+    #[cfg(feature = "opencv")]
+    fn draw_opencv(&self, opencv_image: &mut OpenCVImage, thickness: i32) -> Result<(), Box<dyn std::error::Error>> {
         let mut mat = opencv_image.mat.clone();
 
         let center = core::Point::new(self.cx as i32, self.cy as i32);
         let axes = core::Size::new(self.ax as i32, self.bx as i32);
         let color = core::Scalar::new(0.0, 0.0, 0.0, 0.0); // 黑色
-        // let thickness = (self.ax + self.bx)/100.0 as i32;
-        // 四舍五入
-        let thickness = ((self.ax + self.bx) / 100.0).round() as i32;
 
         if self.full != 0 {
             imgproc::ellipse(
@@ -79,18 +185,10 @@ impl Primitive for Ring {
 
         Ok(())
     }
-
-    fn to_string(&self) -> String {
-        format!("Ring: center=({}, {}), axes=({}, {}), angle={}", 
-                self.cx, self.cy, self.ax, self.bx, self.theta)
-    }
-
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Ring {
     pub x1: c_double,
     pub y1: c_double,
@@ -111,6 +209,284 @@ pub struct Ring {
 
 #[allow(unused)]
 impl Ring {
+    /// Full ellipse centred at `(cx, cy)` with semi-axes `ax` and `bx`, the
+    /// first rotated by `theta` from the x axis.
+    ///
+    /// The ring spans `0..2π`, both end points lie at parametric angle 0 and
+    /// every width is zero.
+    pub fn ellipse(cx: f64, cy: f64, ax: f64, bx: f64, theta: f64) -> Ring {
+        let mut ring = Ring {
+            x1: 0.0,
+            y1: 0.0,
+            x2: 0.0,
+            y2: 0.0,
+            width: 0.0,
+            cx,
+            cy,
+            theta,
+            ax,
+            bx,
+            ang_start: 0.0,
+            ang_end: 2.0 * PI,
+            wmin: 0.0,
+            wmax: 0.0,
+            full: 1,
+        };
+        (ring.x1, ring.y1) = ring.point_at(0.0);
+        (ring.x2, ring.y2) = (ring.x1, ring.y1);
+        ring
+    }
+
+    /// Full circle of radius `r` centred at `(cx, cy)`; see [`Ring::ellipse`].
+    pub fn circle(cx: f64, cy: f64, r: f64) -> Ring {
+        Ring::ellipse(cx, cy, r, r, 0.0)
+    }
+
+    /// The arc of this ring's ellipse from parametric angle `start` to `end`,
+    /// counter-clockwise, with its end points moved to match.
+    pub fn arc(&self, start: f64, end: f64) -> Ring {
+        let mut ring = Ring { ang_start: start, ang_end: end, full: 0, ..*self };
+        (ring.x1, ring.y1) = ring.point_at(start);
+        (ring.x2, ring.y2) = ring.point_at(end);
+        ring
+    }
+
+    /// Angular extent of the ring in radians, `2π` for full rings.
+    pub fn angular_coverage(&self) -> f64 {
+        if self.full != 0 {
+            return 2.0 * PI;
+        }
+        (self.ang_end - self.ang_start).rem_euclid(2.0 * PI)
+    }
+
+    /// Point on the ellipse at parametric angle `t` (in the ellipse frame).
+    ///
+    /// `ang_start` and `ang_end` are parametric angles too, so
+    /// `point_at(ang_start)` is the first end point of an arc.
+    pub fn point_at(&self, t: f64) -> (f64, f64) {
+        let (sin_t, cos_t) = self.theta.sin_cos();
+        let (u, v) = (self.ax * t.cos(), self.bx * t.sin());
+        (self.cx + u * cos_t - v * sin_t, self.cy + u * sin_t + v * cos_t)
+    }
+
+    /// Parametric angle in `(-π, π]` of the ray from the centre through `point`;
+    /// the inverse of [`Ring::point_at`] for points on the ellipse.
+    pub fn parametric_angle(&self, point: (f64, f64)) -> f64 {
+        let (u, v) = self.in_frame(point);
+        (v / self.bx).atan2(u / self.ax)
+    }
+
+    /// `point` in the ellipse frame: centred, with the first axis along `theta`.
+    fn in_frame(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (sin_t, cos_t) = self.theta.sin_cos();
+        let (dx, dy) = (x - self.cx, y - self.cy);
+        (dx * cos_t + dy * sin_t, -dx * sin_t + dy * cos_t)
+    }
+
+    /// `n` points evenly spaced in parametric angle along the ring.
+    ///
+    /// Arcs include both end points; full rings start at `t = 0` and do not
+    /// repeat the first point.
+    pub fn sample(&self, n: usize) -> Vec<(f64, f64)> {
+        let sweep = self.angular_coverage();
+        let (start, intervals) = match (self.full != 0, n) {
+            (_, 0) => return Vec::new(),
+            (true, _) => (0.0, n),
+            (false, 1) => return vec![self.point_at(self.ang_start + sweep / 2.0)],
+            (false, _) => (self.ang_start, n - 1),
+        };
+        (0..n).map(|i| self.point_at(start + sweep * i as f64 / intervals as f64)).collect()
+    }
+
+    /// Area of the whole ellipse, `π·ax·bx`, regardless of the arc.
+    pub fn area(&self) -> f64 {
+        PI * self.ax.abs() * self.bx.abs()
+    }
+
+    /// Perimeter of the whole ellipse by Ramanujan's second approximation.
+    pub fn perimeter(&self) -> f64 {
+        let (a, b) = (self.ax.abs(), self.bx.abs());
+        if a + b == 0.0 {
+            return 0.0;
+        }
+        let h = ((a - b) / (a + b)).powi(2);
+        PI * (a + b) * (1.0 + 3.0 * h / (10.0 + (4.0 - 3.0 * h).sqrt()))
+    }
+
+    /// Eccentricity in `[0, 1)`: 0 for circles, approaching 1 as the ellipse flattens.
+    pub fn eccentricity(&self) -> f64 {
+        let (a, b) = (self.ax.abs(), self.bx.abs());
+        let (major, minor) = (a.max(b), a.min(b));
+        if major == 0.0 {
+            return 0.0;
+        }
+        (1.0 - (minor / major).powi(2)).sqrt()
+    }
+
+    /// Whether `point` lies in the ring's region: the ellipse for full rings,
+    /// the sector from the centre to the arc otherwise (as used by [`Ring::iou`]).
+    pub fn contains(&self, point: (f64, f64)) -> bool {
+        if self.ax <= 0.0 || self.bx <= 0.0 || self.algebraic_distance(point) > 0.0 {
+            return false;
+        }
+        if self.full != 0 || point == (self.cx, self.cy) {
+            return true;
+        }
+        (self.parametric_angle(point) - self.ang_start).rem_euclid(2.0 * PI) <= self.angular_coverage()
+    }
+
+    /// `(u/ax)² + (v/bx)² - 1` for `point` in the ellipse frame, i.e. the conic
+    /// of [`Ring::to_conic`] evaluated at `point`: negative inside, zero on the
+    /// ellipse, positive outside.
+    pub fn algebraic_distance(&self, point: (f64, f64)) -> f64 {
+        let (u, v) = self.in_frame(point);
+        (u / self.ax).powi(2) + (v / self.bx).powi(2) - 1.0
+    }
+
+    /// Euclidean distance from `point` to the nearest point of the whole ellipse.
+    ///
+    /// Uses Eberly's bisection on the ellipse's first quadrant, which stays
+    /// accurate for very flat ellipses and points near the centre.
+    pub fn geometric_distance(&self, point: (f64, f64)) -> f64 {
+        let (u, v) = self.in_frame(point);
+        let (a, b) = (self.ax.abs(), self.bx.abs());
+        // 先把长轴转到第一个坐标上
+        let (e0, e1, y0, y1) = if a >= b { (a, b, u.abs(), v.abs()) } else { (b, a, v.abs(), u.abs()) };
+        if e1 == 0.0 {
+            // 退化为线段 [-e0, e0]
+            return ((y0 - y0.min(e0)).powi(2) + y1 * y1).sqrt();
+        }
+
+        if y1 > 0.0 {
+            if y0 > 0.0 {
+                let (z0, z1) = (y0 / e0, y1 / e1);
+                let g = z0 * z0 + z1 * z1 - 1.0;
+                if g == 0.0 {
+                    return 0.0;
+                }
+                let r0 = (e0 / e1).powi(2);
+                let s = distance_root(r0, z0, z1, g);
+                let (x0, x1) = (r0 * y0 / (s + r0), y1 / (s + 1.0));
+                ((x0 - y0).powi(2) + (x1 - y1).powi(2)).sqrt()
+            } else {
+                (y1 - e1).abs()
+            }
+        } else {
+            let (numer, denom) = (e0 * y0, e0 * e0 - e1 * e1);
+            if numer < denom {
+                let xde0 = numer / denom;
+                let (x0, x1) = (e0 * xde0, e1 * (1.0 - xde0 * xde0).sqrt());
+                ((x0 - y0).powi(2) + x1 * x1).sqrt()
+            } else {
+                (y0 - e0).abs()
+            }
+        }
+    }
+
+    /// Coefficients `[A, B, C, D, E, F]` of `A x² + B xy + C y² + D x + E y + F = 0`,
+    /// scaled so that the left-hand side equals [`Ring::algebraic_distance`].
+    pub fn to_conic(&self) -> [f64; 6] {
+        let (sin_t, cos_t) = self.theta.sin_cos();
+        let (ia, ib) = (1.0 / (self.ax * self.ax), 1.0 / (self.bx * self.bx));
+        let a = cos_t * cos_t * ia + sin_t * sin_t * ib;
+        let b = 2.0 * sin_t * cos_t * (ia - ib);
+        let c = sin_t * sin_t * ia + cos_t * cos_t * ib;
+        let (x0, y0) = (self.cx, self.cy);
+        [
+            a,
+            b,
+            c,
+            -2.0 * a * x0 - b * y0,
+            -b * x0 - 2.0 * c * y0,
+            a * x0 * x0 + b * x0 * y0 + c * y0 * y0 - 1.0,
+        ]
+    }
+
+    /// Full ring on the ellipse `A x² + B xy + C y² + D x + E y + F = 0`, or
+    /// `None` when the conic is not a real ellipse, built with [`Ring::ellipse`].
+    pub fn from_conic(coefficients: [f64; 6]) -> Option<Ring> {
+        let [a, b, c, d, e, f] = coefficients;
+        let det = b * b - 4.0 * a * c;
+        if det >= 0.0 {
+            return None;
+        }
+        let cx = (2.0 * c * d - b * e) / det;
+        let cy = (2.0 * a * e - b * d) / det;
+        let f0 = a * cx * cx + b * cx * cy + c * cy * cy + d * cx + e * cy + f;
+
+        // 二次型 [[A, B/2], [B/2, C]] 的特征分解
+        let theta = 0.5 * b.atan2(a - c);
+        let (sin_t, cos_t) = theta.sin_cos();
+        let l1 = a * cos_t * cos_t + b * sin_t * cos_t + c * sin_t * sin_t;
+        let l2 = a * sin_t * sin_t - b * sin_t * cos_t + c * cos_t * cos_t;
+        let (ax2, bx2) = (-f0 / l1, -f0 / l2);
+        if !(ax2 > 0.0 && bx2 > 0.0 && ax2.is_finite() && bx2.is_finite()) {
+            return None;
+        }
+
+        Some(Ring::ellipse(cx, cy, ax2.sqrt(), bx2.sqrt(), theta))
+    }
+
+    /// Axis-aligned box enclosing the whole ellipse (and therefore any arc of it).
+    pub fn bounding_box(&self) -> BoundingBox {
+        let (sin_t, cos_t) = self.theta.sin_cos();
+        let (a, b) = (self.ax.abs(), self.bx.abs());
+        let half_w = ((a * cos_t).powi(2) + (b * sin_t).powi(2)).sqrt();
+        let half_h = ((a * sin_t).powi(2) + (b * cos_t).powi(2)).sqrt();
+        BoundingBox {
+            min_x: self.cx - half_w,
+            min_y: self.cy - half_h,
+            max_x: self.cx + half_w,
+            max_y: self.cy + half_h,
+        }
+    }
+
+    /// The same ring shifted by `(dx, dy)`, e.g. from crop to full-image coordinates.
+    pub fn translated(&self, dx: f64, dy: f64) -> Ring {
+        Ring {
+            x1: self.x1 + dx,
+            y1: self.y1 + dy,
+            x2: self.x2 + dx,
+            y2: self.y2 + dy,
+            cx: self.cx + dx,
+            cy: self.cy + dy,
+            ..*self
+        }
+    }
+
+    /// The same ring with every length multiplied by `factor` (about the origin).
+    pub fn scaled(&self, factor: f64) -> Ring {
+        Ring {
+            x1: self.x1 * factor,
+            y1: self.y1 * factor,
+            x2: self.x2 * factor,
+            y2: self.y2 * factor,
+            width: self.width * factor,
+            cx: self.cx * factor,
+            cy: self.cy * factor,
+            ax: self.ax * factor,
+            bx: self.bx * factor,
+            wmin: self.wmin * factor,
+            wmax: self.wmax * factor,
+            ..*self
+        }
+    }
+
+    /// Length of the arc from `ang_start` to `ang_end` (the perimeter for full rings).
+    pub fn arc_length(&self) -> f64 {
+        let sweep = self.angular_coverage();
+        let start = if self.full != 0 { 0.0 } else { self.ang_start };
+        let steps = ((sweep * 64.0).ceil() as usize).max(16);
+        let mut prev = self.point_at(start);
+        let mut length = 0.0;
+        for i in 1..=steps {
+            let p = self.point_at(start + sweep * i as f64 / steps as f64);
+            length += ((p.0 - prev.0).powi(2) + (p.1 - prev.1).powi(2)).sqrt();
+            prev = p;
+        }
+        length
+    }
+
     pub fn log_to_file(&self, file: &mut File) -> Result<(), ElsdcError> {
         writeln!(
             file,
@@ -129,20 +505,13 @@ impl Ring {
     }
 
     /// 绘制椭圆到图像
+    ///
+    /// Only touches `img`; use a [`crate::sink::ResultSink`] to log results.
+    #[cfg(feature = "opencv")]
     pub fn draw(&self, img: &mut Mat) -> Result<(), ElsdcError> {
         let color = Scalar::new(0.0, 255.0, 0.0, 0.0);
         let thickness = 2;
 
-        fs::create_dir_all("result")?;
-
-        let mut file = std::fs::OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open("result/out_rust.txt")
-            .map_err(ElsdcError::IoError)?;
-
-        self.log_to_file(&mut file)?;
-
         if self.full != 0 {
             imgproc::ellipse(
                 img,
@@ -177,7 +546,187 @@ impl Ring {
     }
 
     /// 计算两个椭圆的交并比
+    ///
+    /// Full rings cover the whole ellipse, partial rings the sector from
+    /// `ang_start` to `ang_end`. Boundaries are sampled and the regions are
+    /// intersected exactly as convex polygons, so the result does not depend
+    /// on pixel size or OpenCV. With 256 samples per turn each area is
+    /// underestimated by `1 - sin(2π/256) / (2π/256)`, about 1.004e-4.
     pub fn iou(&self, other: &Ring) -> f64 {
+        let pieces_a = self.region_pieces();
+        let pieces_b = other.region_pieces();
+        let area = |pieces: &[Vec<Pt>]| pieces.iter().map(|p| geometry::signed_area(p)).sum::<f64>();
+        let (area_a, area_b) = (area(&pieces_a), area(&pieces_b));
+
+        let mut intersection = 0.0;
+        for a in &pieces_a {
+            for b in &pieces_b {
+                intersection += geometry::convex_intersection_area(a, b);
+            }
+        }
+
+        let union = area_a + area_b - intersection;
+        if union <= 0.0 {
+            0.0
+        } else {
+            (intersection / union).clamp(0.0, 1.0)
+        }
+    }
+
+    /// 按指定方法计算交并比
+    pub fn iou_with(&self, other: &Ring, method: IouMethod) -> Result<f64, ElsdcError> {
+        match method {
+            IouMethod::Analytic => Ok(self.iou(other)),
+            IouMethod::Raster => self.iou_raster(other),
+        }
+    }
+
+    /// 比较两段弧线本身的重合程度
+    ///
+    /// Both curves are sampled evenly along their parameter; a sample counts
+    /// as shared when it lies within `tolerance` pixels of the other curve.
+    /// The result is the shared fraction of all samples (a Dice coefficient
+    /// of the two curves): 1 for identical arcs, 0 for disjoint ones. Unlike
+    /// [`Ring::iou`], two arcs of the same ellipse are compared by how much of
+    /// the curve they share rather than by the area of their pie slices.
+    pub fn arc_overlap(&self, other: &Ring, tolerance: f64) -> f64 {
+        let a = self.arc_samples();
+        let b = other.arc_samples();
+        if a.is_empty() || b.is_empty() {
+            return 0.0;
+        }
+
+        let near = |points: &[Pt], curve: &[Pt]| {
+            points
+                .iter()
+                .filter(|&&p| geometry::polyline_distance(p, curve) <= tolerance)
+                .count()
+        };
+        let shared = near(&a, &b) + near(&b, &a);
+        shared as f64 / (a.len() + b.len()) as f64
+    }
+
+    /// Intersection over union of the angular ranges `[ang_start, ang_end]`.
+    ///
+    /// Only meaningful for rings that share (approximately) the same ellipse;
+    /// see [`Ring::arc_overlap`] for a metric that also checks the geometry.
+    pub fn angular_overlap(&self, other: &Ring) -> f64 {
+        let to_pieces = |ring: &Ring| -> Vec<(f64, f64)> {
+            let sweep = ring.angular_coverage();
+            let start = if ring.full != 0 { 0.0 } else { ring.ang_start.rem_euclid(2.0 * PI) };
+            let end = start + sweep;
+            if end > 2.0 * PI {
+                vec![(start, 2.0 * PI), (0.0, end - 2.0 * PI)]
+            } else {
+                vec![(start, end)]
+            }
+        };
+
+        let (a, b) = (to_pieces(self), to_pieces(other));
+        let mut intersection = 0.0;
+        for &(s1, e1) in &a {
+            for &(s2, e2) in &b {
+                intersection += (e1.min(e2) - s1.max(s2)).max(0.0);
+            }
+        }
+        let union = self.angular_coverage() + other.angular_coverage() - intersection;
+        if union <= 0.0 {
+            0.0
+        } else {
+            (intersection / union).clamp(0.0, 1.0)
+        }
+    }
+
+    /// 按指定度量计算相似度
+    pub fn similarity(&self, other: &Ring, metric: SimilarityMetric) -> Result<f64, ElsdcError> {
+        match metric {
+            SimilarityMetric::Iou(method) => self.iou_with(other, method),
+            SimilarityMetric::ArcOverlap { tolerance } => Ok(self.arc_overlap(other, tolerance)),
+        }
+    }
+
+    /// Evenly spaced samples along the arc; closed for full rings.
+    fn arc_samples(&self) -> Vec<Pt> {
+        if self.ax <= 0.0 || self.bx <= 0.0 {
+            return Vec::new();
+        }
+        let sweep = self.angular_coverage();
+        let start = if self.full != 0 { 0.0 } else { self.ang_start };
+        let steps = ((sweep / (2.0 * PI) * BOUNDARY_SAMPLES as f64).ceil() as usize).max(1);
+        (0..=steps)
+            .map(|i| self.point_at(start + sweep * i as f64 / steps as f64))
+            .collect()
+    }
+
+    /// Convex, counter-clockwise polygons whose union is the ring's region.
+    ///
+    /// A full ring is a single polygon; a sector wider than half a turn is
+    /// split in two so that every piece stays convex.
+    fn region_pieces(&self) -> Vec<Vec<Pt>> {
+        if self.ax <= 0.0 || self.bx <= 0.0 {
+            return Vec::new();
+        }
+
+        if self.full != 0 {
+            let mut poly: Vec<Pt> = (0..BOUNDARY_SAMPLES)
+                .map(|i| self.point_at(2.0 * PI * i as f64 / BOUNDARY_SAMPLES as f64))
+                .collect();
+            geometry::make_ccw(&mut poly);
+            return vec![poly];
+        }
+
+        let sweep = self.angular_coverage();
+        if sweep <= 0.0 {
+            return Vec::new();
+        }
+        let halves = if sweep > PI { 2 } else { 1 };
+        let part = sweep / halves as f64;
+        (0..halves)
+            .map(|h| {
+                let start = self.ang_start + part * h as f64;
+                let steps = ((part / (2.0 * PI) * BOUNDARY_SAMPLES as f64).ceil() as usize).max(2);
+                let mut poly = Vec::with_capacity(steps + 2);
+                poly.push((self.cx, self.cy));
+                poly.extend((0..=steps).map(|i| self.point_at(start + part * i as f64 / steps as f64)));
+                geometry::make_ccw(&mut poly);
+                poly
+            })
+            .collect()
+    }
+
+    /// 用光栅化掩膜计算两个椭圆的交并比（参考实现）
+    ///
+    /// Without OpenCV the regions are rasterised by testing pixel centres
+    /// against the same sectors the analytic IoU uses.
+    #[cfg(not(feature = "opencv"))]
+    pub fn iou_raster(&self, other: &Ring) -> Result<f64, ElsdcError> {
+        let (pieces_a, pieces_b) = (self.region_pieces(), other.region_pieces());
+        let inside = |pieces: &[Vec<Pt>], p: Pt| pieces.iter().any(|piece| geometry::contains_convex(piece, p));
+
+        let (a, b) = (self.bounding_box(), other.bounding_box());
+        let (x0, x1) = (a.min_x.min(b.min_x).floor() as i64, a.max_x.max(b.max_x).ceil() as i64);
+        let (y0, y1) = (a.min_y.min(b.min_y).floor() as i64, a.max_y.max(b.max_y).ceil() as i64);
+
+        let (mut intersection_area, mut union_area) = (0usize, 0usize);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let p = (x as f64, y as f64);
+                let (in_a, in_b) = (inside(&pieces_a, p), inside(&pieces_b, p));
+                intersection_area += (in_a && in_b) as usize;
+                union_area += (in_a || in_b) as usize;
+            }
+        }
+
+        if union_area == 0 {
+            Ok(0.0)
+        } else {
+            Ok(intersection_area as f64 / union_area as f64)
+        }
+    }
+
+    /// 用光栅化掩膜计算两个椭圆的交并比（参考实现）
+    #[cfg(feature = "opencv")]
+    pub fn iou_raster(&self, other: &Ring) -> Result<f64, ElsdcError> {
         // 1. 计算两个椭圆中心的距离
         let dx = self.cx - other.cx;
         let dy = self.cy - other.cy;
@@ -188,14 +737,14 @@ impl Ring {
         let canvas_size = 2.0 * (distance + max_axis);
 
         // 3. 创建画布
-        let mut mask1 = Mat::zeros(canvas_size as i32, canvas_size as i32, opencv::core::CV_8UC1).unwrap().to_mat().unwrap();
-        let mut mask2 = Mat::zeros(canvas_size as i32, canvas_size as i32, opencv::core::CV_8UC1).unwrap().to_mat().unwrap();
+        let mut mask1 = Mat::zeros(canvas_size as i32, canvas_size as i32, opencv::core::CV_8UC1)?.to_mat()?;
+        let mut mask2 = Mat::zeros(canvas_size as i32, canvas_size as i32, opencv::core::CV_8UC1)?.to_mat()?;
 
         // 4. 将椭圆中心移到画布中心
         let center = Point::new((canvas_size / 2.0) as i32, (canvas_size / 2.0) as i32);
 
         // 绘制椭圆
-        if let Err(e) = imgproc::ellipse(
+        imgproc::ellipse(
             &mut mask1,
             center,
             Size::new(self.ax as i32, self.bx as i32),
@@ -206,12 +755,9 @@ impl Ring {
             -1,
             imgproc::LINE_8,
             0,
-        ) {
-            error!("Failed to draw ellipse on mask1: {:?}", e);
-            return 0.0;
-        }
+        )?;
 
-        if let Err(e) = imgproc::ellipse(
+        imgproc::ellipse(
             &mut mask2,
             Point::new((center.x + dx as i32) , (center.y + dy as i32)),
             Size::new(other.ax as i32, other.bx as i32),
@@ -222,64 +768,65 @@ impl Ring {
             -1,
             imgproc::LINE_8,
             0,
-        ) {
-            error!("Failed to draw ellipse on mask2: {:?}", e);
-            return 0.0;
-        }
+        )?;
 
         // 计算交集和并集
         let mut intersection = Mat::default();
         let mut union = Mat::default();
-        if let Err(e) = opencv::core::bitwise_and(&mask1, &mask2, &mut intersection, &Mat::default()) {
-            error!("Failed to calculate intersection: {:?}", e);
-            return 0.0;
-        }
-
-        if let Err(e) = opencv::core::bitwise_or(&mask1, &mask2, &mut union, &Mat::default()) {
-            error!("Failed to calculate union: {:?}", e);
-            return 0.0;
-        }
+        opencv::core::bitwise_and(&mask1, &mask2, &mut intersection, &Mat::default())?;
+        opencv::core::bitwise_or(&mask1, &mask2, &mut union, &Mat::default())?;
 
         // 计算非零像素数量
-        let intersection_area = match opencv::core::count_non_zero(&intersection) {
-            Ok(area) => area as f64,
-            Err(e) => {
-                error!("Failed to count non-zero pixels in intersection: {:?}", e);
-                return 0.0;
-            }
-        };
-
-        let union_area = match opencv::core::count_non_zero(&union) {
-            Ok(area) => area as f64,
-            Err(e) => {
-                error!("Failed to count non-zero pixels in union: {:?}", e);
-                return 0.0;
-            }
-        };
+        let intersection_area = opencv::core::count_non_zero(&intersection)? as f64;
+        let union_area = opencv::core::count_non_zero(&union)? as f64;
 
         // 计算IOU
         if union_area == 0.0 {
-            0.0
+            Ok(0.0)
         } else {
-            intersection_area / union_area
+            Ok(intersection_area / union_area)
         }
     }
 
     /// 生成一组椭圆的兼容性矩阵
+    ///
+    /// Uses the analytic IoU; see [`Ring::generate_sparse_compatibility_matrix`]
+    /// for how pairs are pruned and parallelised.
     pub fn generate_compatibility_matrix(rings: &[Ring]) -> Vec<Vec<f64>> {
-        let n = rings.len();
-        let mut matrix = vec![vec![0.0; n]; n];
-
-        for i in 0..n {
-            for j in i..n {
-                let iou = rings[i].iou(&rings[j]);
-                matrix[i][j] = iou;
-                matrix[j][i] = iou; // 矩阵是对称的
-            }
-        }
+        Self::sparse_iou_matrix(rings).to_dense()
+    }
+
+    /// 使用指定的相似度度量生成兼容性矩阵
+    pub fn generate_compatibility_matrix_with(
+        rings: &[Ring],
+        metric: SimilarityMetric,
+    ) -> Result<Vec<Vec<f64>>, ElsdcError> {
+        Ok(Self::generate_sparse_compatibility_matrix(rings, metric)?.to_dense())
+    }
+}
 
-        matrix
+/// Root `s` of `(r0·z0 / (s + r0))² + (z1 / (s + 1))² = 1` by bisection,
+/// for [`Ring::geometric_distance`].
+fn distance_root(r0: f64, z0: f64, z1: f64, g: f64) -> f64 {
+    let n0 = r0 * z0;
+    let mut s0 = z1 - 1.0;
+    let mut s1 = if g < 0.0 { 0.0 } else { n0.hypot(z1) - 1.0 };
+    let mut s = 0.0;
+    for _ in 0..200 {
+        s = (s0 + s1) / 2.0;
+        if s == s0 || s == s1 {
+            break;
+        }
+        let g = (n0 / (s + r0)).powi(2) + (z1 / (s + 1.0)).powi(2) - 1.0;
+        if g > 0.0 {
+            s0 = s;
+        } else if g < 0.0 {
+            s1 = s;
+        } else {
+            break;
+        }
     }
+    s
 }
 
 #[cfg(test)]
@@ -389,6 +936,86 @@ mod tests {
     }
 
     #[test]
+    fn test_iou_sub_pixel_rings() {
+        // 两个半径 0.4 的圆，圆心距离 0.4：与解析解比较
+        let (r, d) = (0.4_f64, 0.4_f64);
+        let lens = 2.0 * r * r * (d / (2.0 * r)).acos() - d / 2.0 * (4.0 * r * r - d * d).sqrt();
+        let disk = std::f64::consts::PI * r * r;
+        let expected = lens / (2.0 * disk - lens);
+
+        let iou = Ring::circle(0.0, 0.0, r).iou(&Ring::circle(d, 0.0, r));
+        assert!((iou - expected).abs() < 1e-3, "iou={} expected={}", iou, expected);
+    }
+
+    #[test]
+    fn test_iou_half_arc_against_full() {
+        let full = Ring::circle(10.0, 10.0, 5.0);
+        let mut half = full;
+        half.full = 0;
+        half.ang_start = 0.0;
+        half.ang_end = std::f64::consts::PI;
+        assert!((full.iou(&half) - 0.5).abs() < 1e-3);
+
+        // 跨越 ±π 的弧：从 3π/2 到 π/2，共半圈
+        let mut wrapped = half;
+        wrapped.ang_start = 1.5 * std::f64::consts::PI;
+        wrapped.ang_end = 0.5 * std::f64::consts::PI;
+        assert!((full.iou(&wrapped) - 0.5).abs() < 1e-3);
+        // 两个半圆相交于四分之一圆：1/4 ÷ 3/4
+        assert!((half.iou(&wrapped) - 1.0 / 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_arc_overlap_same_ellipse() {
+        let full = Ring::circle(50.0, 50.0, 20.0);
+        let mut first_half = full;
+        first_half.full = 0;
+        first_half.ang_start = 0.0;
+        first_half.ang_end = std::f64::consts::PI;
+        let mut second_half = first_half;
+        second_half.ang_start = std::f64::consts::PI;
+        second_half.ang_end = 2.0 * std::f64::consts::PI;
+
+        assert!((first_half.arc_overlap(&first_half, 1.0) - 1.0).abs() < 1e-9);
+        // 两个不相交的半圆弧只在端点处接触
+        assert!(first_half.arc_overlap(&second_half, 1.0) < 0.05);
+        // 半圆弧与整圆共享半圆：2 * 1/2 / (1/2 + 1)
+        assert!((first_half.arc_overlap(&full, 1.0) - 2.0 / 3.0).abs() < 0.02);
+
+        assert!((first_half.angular_overlap(&full) - 0.5).abs() < 1e-9);
+        assert_eq!(first_half.angular_overlap(&second_half), 0.0);
+    }
+
+    #[test]
+    fn test_arc_overlap_tolerance() {
+        let inner = Ring::circle(50.0, 50.0, 20.0);
+        let outer = Ring::circle(50.0, 50.0, 21.5);
+        assert_eq!(inner.arc_overlap(&outer, 1.0), 0.0);
+        assert_eq!(inner.arc_overlap(&outer, 2.0), 1.0);
+        // IoU 无法区分同心圆的曲线是否重合
+        assert!(inner.iou(&outer) > 0.8);
+    }
+
+    #[test]
+    fn test_compatibility_matrix_with_metric() {
+        let rings = [Ring::circle(0.0, 0.0, 10.0), Ring::circle(1.0, 0.0, 10.0), Ring::circle(100.0, 0.0, 10.0)];
+        let iou = Ring::generate_compatibility_matrix_with(&rings, SimilarityMetric::default()).unwrap();
+        assert_eq!(iou, Ring::generate_compatibility_matrix(&rings));
+
+        let arcs = Ring::generate_compatibility_matrix_with(
+            &rings,
+            SimilarityMetric::ArcOverlap { tolerance: 0.5 },
+        )
+        .unwrap();
+        assert_eq!(arcs[0][0], 1.0);
+        // 圆心偏移 1 像素：约三分之一的曲线落在 0.5 像素以内
+        assert!(arcs[0][1] > 0.25 && arcs[0][1] < 0.45);
+        assert_eq!(arcs[0][2], 0.0);
+        assert_eq!(arcs[1][0], arcs[0][1]);
+    }
+
+    #[test]
+    #[cfg(feature = "opencv")]
     fn test_ring_draw() {
         let ring = Ring {
             x1: 0.0,
@@ -430,7 +1057,108 @@ mod tests {
 
         // 可选：保存图像以进行视觉检查
         // 确保 result 目录存在
-        fs::create_dir_all("result").expect("Failed to create result directory");
+        std::fs::create_dir_all("result").expect("Failed to create result directory");
         opencv::imgcodecs::imwrite("result/test_ring_draw.png", &img, &opencv::core::Vector::new()).expect("Failed to write image");
     }
+
+    #[test]
+    fn test_ring_draw_gray_image() {
+        let ring = Ring { full: 0, ang_start: 0.0, ang_end: PI / 2.0, ..Ring::circle(50.0, 50.0, 20.0) };
+        let mut image = GrayImage::from_vec(100, 100, vec![255.0; 100 * 100]).unwrap();
+        Primitive::draw(&ring, &mut image).unwrap();
+
+        // 只有右下方的四分之一圆弧被绘制
+        assert_eq!(image.get_pixel(70, 50).unwrap(), 0.0);
+        assert_eq!(image.get_pixel(50, 70).unwrap(), 0.0);
+        assert_eq!(image.get_pixel(30, 50).unwrap(), 255.0);
+        assert_eq!(image.get_pixel(50, 30).unwrap(), 255.0);
+    }
+
+    #[test]
+    fn test_iou_raster_close_to_analytic() {
+        let (a, b) = (Ring::circle(0.0, 0.0, 20.0), Ring::circle(10.0, 0.0, 20.0));
+        let raster = a.iou_raster(&b).unwrap();
+        assert!((raster - a.iou(&b)).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_scaled_and_translated() {
+        let ring = Ring { x1: 30.0, y1: 10.0, wmin: 1.0, wmax: 2.0, ..Ring::circle(10.0, 10.0, 20.0) };
+        let moved = ring.scaled(2.0).translated(0.5, -1.0);
+        assert_eq!((moved.cx, moved.cy, moved.ax, moved.bx), (20.5, 19.0, 40.0, 40.0));
+        assert_eq!((moved.x1, moved.y1, moved.wmin, moved.wmax), (60.5, 19.0, 2.0, 4.0));
+        assert_eq!((moved.theta, moved.full), (ring.theta, ring.full));
+        let (x, y) = moved.point_at(0.0);
+        assert!((x - 60.5).abs() < 1e-12 && (y - 19.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_constructors() {
+        let e = Ring::ellipse(10.0, 5.0, 4.0, 2.0, PI / 2.0);
+        assert_eq!(e.full, 1);
+        assert!((e.x1 - 10.0).abs() < 1e-12 && (e.y1 - 9.0).abs() < 1e-12);
+        assert_eq!((e.x1, e.y1), (e.x2, e.y2));
+
+        let arc = Ring::circle(0.0, 0.0, 2.0).arc(0.0, PI / 2.0);
+        assert_eq!(arc.full, 0);
+        assert!((arc.angular_coverage() - PI / 2.0).abs() < 1e-12);
+        assert!((arc.x2).abs() < 1e-12 && (arc.y2 - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_measurements() {
+        let c = Ring::circle(0.0, 0.0, 10.0);
+        assert!((c.area() - 100.0 * PI).abs() < 1e-9);
+        assert!((c.perimeter() - 20.0 * PI).abs() < 1e-9);
+        assert_eq!(c.eccentricity(), 0.0);
+
+        let e = Ring { ax: 30.0, bx: 10.0, theta: 0.4, ..c };
+        assert!((e.perimeter() - e.arc_length()).abs() / e.perimeter() < 1e-3);
+        assert!((e.eccentricity() - (8.0f64 / 9.0).sqrt()).abs() < 1e-12);
+
+        // 四分之一圆弧：采样包含两个端点
+        let arc = Ring { ang_start: 0.0, ang_end: PI / 2.0, full: 0, ..c };
+        assert!((arc.angular_coverage() - PI / 2.0).abs() < 1e-12);
+        let samples = arc.sample(3);
+        assert_eq!(samples.len(), 3);
+        assert!((samples[0].0 - 10.0).abs() < 1e-12 && (samples[2].1 - 10.0).abs() < 1e-12);
+        assert_eq!(c.sample(4).len(), 4);
+        assert!(arc.sample(0).is_empty());
+
+        assert!(arc.contains((3.0, 3.0)) && !arc.contains((-3.0, 3.0)) && !arc.contains((9.0, 9.0)));
+        assert!(c.contains((-3.0, 3.0)));
+    }
+
+    #[test]
+    fn test_distances_and_conic() {
+        let e = Ring { ax: 30.0, bx: 10.0, theta: 0.4, ..Ring::circle(5.0, -2.0, 1.0) };
+        let on = e.point_at(1.3);
+        assert!(e.algebraic_distance(on).abs() < 1e-12 && e.geometric_distance(on) < 1e-9);
+        assert!((e.parametric_angle(on) - 1.3).abs() < 1e-12);
+        assert!(e.algebraic_distance((5.0, -2.0)) < 0.0);
+        // 中心到椭圆的最近距离是短半轴
+        assert!((e.geometric_distance((5.0, -2.0)) - 10.0).abs() < 1e-9);
+
+        // 沿法线向外移动 d，几何距离就是 d
+        let normal = {
+            let (x, y) = e.in_frame(on);
+            let (nu, nv) = (x / 900.0, y / 100.0);
+            let len = nu.hypot(nv);
+            let (sin_t, cos_t) = e.theta.sin_cos();
+            ((nu * cos_t - nv * sin_t) / len, (nu * sin_t + nv * cos_t) / len)
+        };
+        let off = (on.0 + 2.5 * normal.0, on.1 + 2.5 * normal.1);
+        assert!((e.geometric_distance(off) - 2.5).abs() < 1e-9);
+
+        let conic = e.to_conic();
+        let [a, b, c, d, f, g] = conic;
+        let (x, y) = off;
+        assert!((a * x * x + b * x * y + c * y * y + d * x + f * y + g - e.algebraic_distance(off)).abs() < 1e-9);
+
+        let back = Ring::from_conic(conic.map(|v| v * -3.0)).unwrap();
+        assert!((back.cx - 5.0).abs() < 1e-9 && (back.cy + 2.0).abs() < 1e-9);
+        assert!((back.ax - 30.0).abs() < 1e-9 && (back.bx - 10.0).abs() < 1e-9);
+        assert!((back.theta - 0.4).abs() < 1e-9 && back.full == 1);
+        assert!(Ring::from_conic([1.0, 0.0, -1.0, 0.0, 0.0, -1.0]).is_none());
+    }
 }
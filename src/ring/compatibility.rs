@@ -0,0 +1,291 @@
+/**
+ * File: /src/ring/compatibility.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 5:02:31 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use rayon::prelude::*;
+
+use super::{BoundingBox, IouMethod, Ring, SimilarityMetric};
+use crate::ElsdcError;
+
+/// Symmetric matrix that only stores non-zero entries.
+///
+/// Entries are kept for `i <= j`, sorted by `(i, j)`; lookups are symmetric.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SparseMatrix {
+    size: usize,
+    entries: Vec<(usize, usize, f64)>,
+}
+
+impl SparseMatrix {
+    /// Builds a matrix from upper-triangle entries; zeros are dropped.
+    pub fn from_entries(size: usize, mut entries: Vec<(usize, usize, f64)>) -> Self {
+        for e in entries.iter_mut() {
+            if e.0 > e.1 {
+                std::mem::swap(&mut e.0, &mut e.1);
+            }
+        }
+        entries.retain(|&(_, _, v)| v != 0.0);
+        entries.sort_by_key(|e| (e.0, e.1));
+        entries.dedup_by(|a, b| (a.0, a.1) == (b.0, b.1));
+        Self { size, entries }
+    }
+
+    /// Number of rows (and columns).
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of stored (non-zero) entries of the upper triangle.
+    pub fn nnz(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let key = if i <= j { (i, j) } else { (j, i) };
+        self.entries
+            .binary_search_by(|e| (e.0, e.1).cmp(&key))
+            .map(|k| self.entries[k].2)
+            .unwrap_or(0.0)
+    }
+
+    /// Non-zero entries `(i, j, value)` with `i <= j`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// Non-zero entries of row `i` as `(column, value)`.
+    pub fn row(&self, i: usize) -> Vec<(usize, f64)> {
+        self.entries
+            .iter()
+            .filter_map(|&(a, b, v)| {
+                if a == i {
+                    Some((b, v))
+                } else if b == i {
+                    Some((a, v))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_dense(&self) -> Vec<Vec<f64>> {
+        let mut matrix = vec![vec![0.0; self.size]; self.size];
+        for &(i, j, v) in &self.entries {
+            matrix[i][j] = v;
+            matrix[j][i] = v;
+        }
+        matrix
+    }
+}
+
+/// Uniform grid over ring bounding boxes, used to skip pairs that cannot overlap.
+#[derive(Debug)]
+pub struct RingIndex {
+    boxes: Vec<BoundingBox>,
+    cell: f64,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl RingIndex {
+    /// Indexes `rings`, growing every bounding box by `margin` pixels.
+    pub fn new(rings: &[Ring], margin: f64) -> Self {
+        let boxes: Vec<BoundingBox> = rings.iter().map(|r| r.bounding_box().expand(margin)).collect();
+
+        // 网格边长取包围盒的平均尺寸，每个椭圆大约落在常数个格子里
+        let finite: Vec<&BoundingBox> = boxes.iter().filter(|b| b.is_finite()).collect();
+        let mean_extent = if finite.is_empty() {
+            1.0
+        } else {
+            finite.iter().map(|b| b.width().max(b.height())).sum::<f64>() / finite.len() as f64
+        };
+        let cell = mean_extent.max(1.0);
+
+        let mut index = Self { boxes, cell, grid: HashMap::new() };
+        for i in 0..index.boxes.len() {
+            if index.boxes[i].is_finite() {
+                for key in index.cells(&index.boxes[i]) {
+                    index.grid.entry(key).or_default().push(i);
+                }
+            }
+        }
+        index
+    }
+
+    fn cells(&self, b: &BoundingBox) -> impl Iterator<Item = (i64, i64)> {
+        let cell = self.cell;
+        let (x0, x1) = ((b.min_x / cell).floor() as i64, (b.max_x / cell).floor() as i64);
+        let (y0, y1) = ((b.min_y / cell).floor() as i64, (b.max_y / cell).floor() as i64);
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+
+    /// Indices of the rings whose (expanded) box intersects `query`.
+    pub fn query(&self, query: &BoundingBox) -> Vec<usize> {
+        if !query.is_finite() {
+            return Vec::new();
+        }
+        let mut hits: Vec<usize> = self
+            .cells(query)
+            .filter_map(|key| self.grid.get(&key))
+            .flatten()
+            .copied()
+            .filter(|&i| self.boxes[i].intersects(query))
+            .collect();
+        hits.sort_unstable();
+        hits.dedup();
+        hits
+    }
+
+    /// All pairs `(i, j)` with `i < j` whose boxes intersect.
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<(usize, usize)> = self
+            .grid
+            .values()
+            .flat_map(|bucket| {
+                bucket.iter().enumerate().flat_map(move |(k, &i)| {
+                    bucket[k + 1..].iter().map(move |&j| if i < j { (i, j) } else { (j, i) })
+                })
+            })
+            .filter(|&(i, j)| self.boxes[i].intersects(&self.boxes[j]))
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+/// Fills a sparse matrix with `similarity` for the diagonal and every pair
+/// whose boxes (grown by `margin`) intersect. Pairs run on the rayon pool.
+fn sparse_with<E, F>(rings: &[Ring], margin: f64, similarity: F) -> Result<SparseMatrix, E>
+where
+    E: Send,
+    F: Fn(&Ring, &Ring) -> Result<f64, E> + Sync,
+{
+    let index = RingIndex::new(rings, margin);
+    let pairs: Vec<(usize, usize)> = (0..rings.len())
+        .map(|i| (i, i))
+        .chain(index.candidate_pairs())
+        .collect();
+
+    let entries = pairs
+        .into_par_iter()
+        .map(|(i, j)| similarity(&rings[i], &rings[j]).map(|v| (i, j, v)))
+        .collect::<Result<Vec<_>, E>>()?;
+
+    Ok(SparseMatrix::from_entries(rings.len(), entries))
+}
+
+/// Box margin that keeps the pruning exact for `metric`.
+///
+/// Raster IoU draws both masks on whole pixels (the OpenCV version also
+/// truncates the centre offset), so its regions can reach one pixel past the
+/// exact bounding boxes.
+pub(crate) fn margin_for(metric: SimilarityMetric) -> f64 {
+    match metric {
+        SimilarityMetric::Iou(IouMethod::Analytic) => 0.0,
+        SimilarityMetric::Iou(IouMethod::Raster) => 1.0,
+        SimilarityMetric::ArcOverlap { tolerance } => tolerance.max(0.0),
+    }
+}
+
+impl Ring {
+    /// 生成稀疏兼容性矩阵
+    ///
+    /// Pairs whose bounding boxes, grown by the reach of `metric`, cannot
+    /// overlap are skipped (their similarity is exactly zero); the remaining
+    /// pairs are evaluated in parallel on the current rayon thread pool. Wrap
+    /// the call in `ThreadPool::install` to control the number of threads.
+    pub fn generate_sparse_compatibility_matrix(
+        rings: &[Ring],
+        metric: SimilarityMetric,
+    ) -> Result<SparseMatrix, ElsdcError> {
+        sparse_with(rings, margin_for(metric), |a, b| a.similarity(b, metric))
+    }
+
+    /// Sparse analytic IoU matrix; cannot fail.
+    pub(crate) fn sparse_iou_matrix(rings: &[Ring]) -> SparseMatrix {
+        match sparse_with::<Infallible, _>(rings, 0.0, |a, b| Ok(a.iou(b))) {
+            Ok(matrix) => matrix,
+            Err(never) => match never {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference O(n²) matrix without any pruning.
+    fn brute_force(rings: &[Ring], metric: SimilarityMetric) -> Vec<Vec<f64>> {
+        let n = rings.len();
+        let mut m = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in 0..n {
+                m[i][j] = rings[i].similarity(&rings[j], metric).unwrap();
+            }
+        }
+        m
+    }
+
+    fn scene() -> Vec<Ring> {
+        let mut rings = Vec::new();
+        for k in 0..24 {
+            let (x, y) = ((k % 6) as f64 * 37.0, (k / 6) as f64 * 41.0);
+            rings.push(Ring::circle(x, y, 5.0 + (k % 5) as f64 * 6.0));
+        }
+        rings
+    }
+
+    #[test]
+    fn test_pruned_matches_brute_force() {
+        let rings = scene();
+        for metric in [SimilarityMetric::default(), SimilarityMetric::ArcOverlap { tolerance: 2.0 }] {
+            assert_pruned_matches(&rings, metric);
+        }
+        // 光栅 IoU 较慢，只取一行圆
+        assert_pruned_matches(&rings[..6], SimilarityMetric::Iou(IouMethod::Raster));
+    }
+
+    fn assert_pruned_matches(rings: &[Ring], metric: SimilarityMetric) {
+        let sparse = Ring::generate_sparse_compatibility_matrix(rings, metric).unwrap();
+        let expected = brute_force(rings, metric);
+        let dense = sparse.to_dense();
+        for i in 0..rings.len() {
+            for j in 0..rings.len() {
+                assert!((dense[i][j] - expected[i][j]).abs() < 1e-9, "{:?} mismatch at ({}, {})", metric, i, j);
+            }
+        }
+        assert!(sparse.nnz() < rings.len() * rings.len() / 2);
+    }
+
+    #[test]
+    fn test_index_query() {
+        let rings = [Ring::circle(0.0, 0.0, 5.0), Ring::circle(8.0, 0.0, 5.0), Ring::circle(100.0, 100.0, 5.0)];
+        let index = RingIndex::new(&rings, 0.0);
+        assert_eq!(index.candidate_pairs(), vec![(0, 1)]);
+        assert_eq!(index.query(&rings[2].bounding_box()), vec![2]);
+    }
+
+    #[test]
+    fn test_sparse_matrix_accessors() {
+        let m = SparseMatrix::from_entries(3, vec![(1, 0, 0.5), (2, 2, 1.0), (0, 2, 0.0)]);
+        assert_eq!(m.nnz(), 2);
+        assert_eq!(m.get(0, 1), 0.5);
+        assert_eq!(m.get(1, 0), 0.5);
+        assert_eq!(m.get(0, 2), 0.0);
+        assert_eq!(m.row(0), vec![(1, 0.5)]);
+        assert_eq!(m.to_dense()[2][2], 1.0);
+    }
+}
@@ -0,0 +1,447 @@
+/**
+ * File: /src/ring/nms.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 7:40:16 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::f64::consts::PI;
+
+use super::{Ring, RingIndex};
+use crate::geometry::{self, Pt};
+use crate::ElsdcError;
+
+/// Ranking used by [`nms`]; the highest score wins.
+#[derive(Clone, Copy, Debug)]
+pub enum Score<'a> {
+    /// Angular coverage of the arc, `2π` for full rings.
+    ArcCoverage,
+    /// Arc length in pixels.
+    ArcLength,
+    /// One score per ring supplied by the caller, e.g. `-log10(NFA)`.
+    Provided(&'a [f64]),
+}
+
+impl Score<'_> {
+    pub(crate) fn values(&self, rings: &[Ring]) -> Result<Vec<f64>, ElsdcError> {
+        match self {
+            Score::ArcCoverage => Ok(rings.iter().map(|r| r.angular_coverage()).collect()),
+            Score::ArcLength => Ok(rings.iter().map(|r| r.arc_length()).collect()),
+            Score::Provided(scores) if scores.len() == rings.len() => Ok(scores.to_vec()),
+            Score::Provided(scores) => Err(ElsdcError::DetectionError(format!(
+                "Got {} scores for {} rings",
+                scores.len(),
+                rings.len()
+            ))),
+        }
+    }
+}
+
+/// 非极大值抑制
+///
+/// Greedily keeps the best-scoring ring and suppresses every remaining ring
+/// whose IoU with it exceeds `iou_threshold`. Returns the indices of the
+/// kept rings, best first.
+pub fn nms(rings: &[Ring], score: Score, iou_threshold: f64) -> Result<Vec<usize>, ElsdcError> {
+    let scores = score.values(rings)?;
+    let mut order: Vec<usize> = (0..rings.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+
+    let index = RingIndex::new(rings, 0.0);
+    let mut suppressed = vec![false; rings.len()];
+    let mut keep = Vec::new();
+    for &i in &order {
+        if suppressed[i] {
+            continue;
+        }
+        keep.push(i);
+        for j in index.query(&rings[i].bounding_box()) {
+            if j != i && !suppressed[j] && rings[i].iou(&rings[j]) > iou_threshold {
+                suppressed[j] = true;
+            }
+        }
+    }
+    Ok(keep)
+}
+
+/// Parameters for grouping and merging arcs of the same physical ellipse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergeParams {
+    /// Maximum distance (pixels) of an arc sample from the other ellipse.
+    pub tolerance: f64,
+    /// Fraction of samples of *each* arc that must lie on the other ellipse.
+    pub min_agreement: f64,
+    /// A merged ring whose largest uncovered angular gap is below this (radians) becomes full.
+    pub full_gap: f64,
+}
+
+impl Default for MergeParams {
+    fn default() -> Self {
+        Self { tolerance: 2.0, min_agreement: 0.8, full_gap: PI / 12.0 }
+    }
+}
+
+/// Groups rings whose geometry agrees: the samples of each arc lie (mostly)
+/// on the complete ellipse of the other. Groups are connected components of
+/// that relation, ordered by their smallest index.
+pub fn group_arcs(rings: &[Ring], params: &MergeParams) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..rings.len()).collect();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        let mut k = i;
+        while parent[k] != root {
+            let next = parent[k];
+            parent[k] = root;
+            k = next;
+        }
+        root
+    }
+
+    let samples: Vec<Vec<Pt>> = rings.iter().map(|r| r.arc_samples()).collect();
+    let closed: Vec<Vec<Pt>> = rings
+        .iter()
+        .map(|r| Ring { full: 1, ..*r }.arc_samples())
+        .collect();
+    let on_curve = |points: &[Pt], curve: &[Pt]| {
+        if points.is_empty() {
+            return 0.0;
+        }
+        let near = points
+            .iter()
+            .filter(|&&p| geometry::polyline_distance(p, curve) <= params.tolerance)
+            .count();
+        near as f64 / points.len() as f64
+    };
+
+    let index = RingIndex::new(rings, params.tolerance);
+    for (i, j) in index.candidate_pairs() {
+        if on_curve(&samples[i], &closed[j]) >= params.min_agreement
+            && on_curve(&samples[j], &closed[i]) >= params.min_agreement
+        {
+            let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+            parent[ri.max(rj)] = ri.min(rj);
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of = vec![usize::MAX; rings.len()];
+    for i in 0..rings.len() {
+        let root = find(&mut parent, i);
+        if group_of[root] == usize::MAX {
+            group_of[root] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of[root]].push(i);
+    }
+    groups
+}
+
+/// Merges each group of [`group_arcs`] into one ring re-fitted from the
+/// sampled points of its arcs. Rings without partners are returned unchanged.
+pub fn merge_arcs(rings: &[Ring], params: &MergeParams) -> Vec<Ring> {
+    let supports: Vec<Vec<Pt>> = rings.iter().map(|r| r.arc_samples()).collect();
+    merge_groups(rings, &supports, params)
+}
+
+/// Like [`merge_arcs`], but re-fits from the given supporting points of every
+/// ring (e.g. its pixels in the label map). Empty supports fall back to
+/// samples of the arc.
+pub fn merge_arcs_with_support(
+    rings: &[Ring],
+    supports: &[Vec<(f64, f64)>],
+    params: &MergeParams,
+) -> Result<Vec<Ring>, ElsdcError> {
+    if supports.len() != rings.len() {
+        return Err(ElsdcError::DetectionError(format!(
+            "Got {} supports for {} rings",
+            supports.len(),
+            rings.len()
+        )));
+    }
+    let supports: Vec<Vec<Pt>> = rings
+        .iter()
+        .zip(supports)
+        .map(|(r, s)| if s.is_empty() { r.arc_samples() } else { s.clone() })
+        .collect();
+    Ok(merge_groups(rings, &supports, params))
+}
+
+fn merge_groups(rings: &[Ring], supports: &[Vec<Pt>], params: &MergeParams) -> Vec<Ring> {
+    group_arcs(rings, params)
+        .into_iter()
+        .map(|group| {
+            if group.len() == 1 {
+                return rings[group[0]];
+            }
+            let points: Vec<Pt> = group.iter().flat_map(|&i| supports[i].iter().copied()).collect();
+            let members: Vec<&Ring> = group.iter().map(|&i| &rings[i]).collect();
+            refit(&members, &points, params).unwrap_or_else(|| {
+                // 拟合失败时保留覆盖角度最大的弧
+                **members
+                    .iter()
+                    .max_by(|a, b| a.angular_coverage().total_cmp(&b.angular_coverage()))
+                    .expect("groups are never empty")
+            })
+        })
+        .collect()
+}
+
+/// Fits an ellipse to `points` and derives the arc covered by them.
+fn refit(members: &[&Ring], points: &[Pt], params: &MergeParams) -> Option<Ring> {
+    let fitted = fit_ellipse(points)?;
+
+    // 支撑点在拟合椭圆上的参数角，最大空隙之外即为弧段
+    let mut angles: Vec<f64> = points.iter().map(|&p| fitted.parametric_angle(p)).collect();
+    angles.sort_by(|a, b| a.total_cmp(b));
+
+    let mut gap = (angles[0] + 2.0 * PI - angles[angles.len() - 1], angles.len() - 1);
+    for k in 0..angles.len() - 1 {
+        let g = angles[k + 1] - angles[k];
+        if g > gap.0 {
+            gap = (g, k);
+        }
+    }
+
+    let full = gap.0 < params.full_gap;
+    let (ang_start, ang_end) = if full {
+        (-PI, PI)
+    } else {
+        (angles[(gap.1 + 1) % angles.len()], angles[gap.1])
+    };
+
+    let mut ring = Ring {
+        width: members.iter().map(|r| r.width).sum::<f64>() / members.len() as f64,
+        ang_start,
+        ang_end,
+        wmin: members.iter().map(|r| r.wmin).fold(f64::INFINITY, f64::min),
+        wmax: members.iter().map(|r| r.wmax).fold(f64::NEG_INFINITY, f64::max),
+        full: full as i32,
+        ..fitted
+    };
+    (ring.x1, ring.y1) = ring.point_at(ang_start);
+    (ring.x2, ring.y2) = ring.point_at(ang_end);
+    Some(ring)
+}
+
+/// Direct least-squares ellipse fit (Fitzgibbon, in the numerically stable
+/// form of Halir & Flusser), as a full ring with zero widths.
+fn fit_ellipse(points: &[Pt]) -> Option<Ring> {
+    if points.len() < 5 {
+        return None;
+    }
+
+    // 归一化坐标以改善条件数
+    let n = points.len() as f64;
+    let (mx, my) = points.iter().fold((0.0, 0.0), |acc, p| (acc.0 + p.0 / n, acc.1 + p.1 / n));
+    let scale = (points.iter().map(|p| (p.0 - mx).powi(2) + (p.1 - my).powi(2)).sum::<f64>() / n).sqrt();
+    if scale <= 0.0 {
+        return None;
+    }
+
+    let mut s1 = [[0.0; 3]; 3];
+    let mut s2 = [[0.0; 3]; 3];
+    let mut s3 = [[0.0; 3]; 3];
+    for p in points {
+        let (x, y) = ((p.0 - mx) / scale, (p.1 - my) / scale);
+        let d1 = [x * x, x * y, y * y];
+        let d2 = [x, y, 1.0];
+        for r in 0..3 {
+            for c in 0..3 {
+                s1[r][c] += d1[r] * d1[c];
+                s2[r][c] += d1[r] * d2[c];
+                s3[r][c] += d2[r] * d2[c];
+            }
+        }
+    }
+
+    // T = -S3^-1 S2^T, M = C1^-1 (S1 + S2 T)
+    let s3_inv = invert3(&s3)?;
+    let s2_t = transpose3(&s2);
+    let mut t = mul3(&s3_inv, &s2_t);
+    for row in t.iter_mut() {
+        for v in row.iter_mut() {
+            *v = -*v;
+        }
+    }
+    let reduced = add3(&s1, &mul3(&s2, &t));
+    let m = [
+        [reduced[2][0] / 2.0, reduced[2][1] / 2.0, reduced[2][2] / 2.0],
+        [-reduced[1][0], -reduced[1][1], -reduced[1][2]],
+        [reduced[0][0] / 2.0, reduced[0][1] / 2.0, reduced[0][2] / 2.0],
+    ];
+
+    let a1 = eigenvectors3(&m)
+        .into_iter()
+        .find(|v| 4.0 * v[0] * v[2] - v[1] * v[1] > 0.0)?;
+    let a2 = [
+        t[0][0] * a1[0] + t[0][1] * a1[1] + t[0][2] * a1[2],
+        t[1][0] * a1[0] + t[1][1] * a1[1] + t[1][2] * a1[2],
+        t[2][0] * a1[0] + t[2][1] * a1[1] + t[2][2] * a1[2],
+    ];
+
+    let ring = Ring::from_conic([a1[0], a1[1], a1[2], a2[0], a2[1], a2[2]])?;
+    Some(ring.scaled(scale).translated(mx, my))
+}
+
+type Mat3 = [[f64; 3]; 3];
+
+fn transpose3(m: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+}
+
+fn mul3(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn add3(a: &Mat3, b: &Mat3) -> Mat3 {
+    std::array::from_fn(|i| std::array::from_fn(|j| a[i][j] + b[i][j]))
+}
+
+fn det3(m: &Mat3) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+fn invert3(m: &Mat3) -> Option<Mat3> {
+    let det = det3(m);
+    if det.abs() < 1e-300 {
+        return None;
+    }
+    // 伴随矩阵除以行列式
+    let r = std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let (i1, i2) = ((j + 1) % 3, (j + 2) % 3);
+            let (j1, j2) = ((i + 1) % 3, (i + 2) % 3);
+            (m[i1][j1] * m[i2][j2] - m[i1][j2] * m[i2][j1]) / det
+        })
+    });
+    Some(r)
+}
+
+fn cross3(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+/// Eigenvectors of the real eigenvalues of a (not necessarily symmetric) 3x3 matrix.
+fn eigenvectors3(m: &Mat3) -> Vec<[f64; 3]> {
+    // 特征多项式 λ³ + p λ² + q λ + r
+    let p = -(m[0][0] + m[1][1] + m[2][2]);
+    let q = m[0][0] * m[1][1] - m[0][1] * m[1][0] + m[0][0] * m[2][2] - m[0][2] * m[2][0]
+        + m[1][1] * m[2][2] - m[1][2] * m[2][1];
+    let r = -det3(m);
+
+    solve_cubic(p, q, r)
+        .into_iter()
+        .filter_map(|lambda| {
+            let rows = [
+                [m[0][0] - lambda, m[0][1], m[0][2]],
+                [m[1][0], m[1][1] - lambda, m[1][2]],
+                [m[2][0], m[2][1], m[2][2] - lambda],
+            ];
+            [cross3(rows[0], rows[1]), cross3(rows[0], rows[2]), cross3(rows[1], rows[2])]
+                .into_iter()
+                .max_by(|a, b| norm3(a).total_cmp(&norm3(b)))
+                .filter(|v| norm3(v) > 0.0)
+        })
+        .collect()
+}
+
+fn norm3(v: &[f64; 3]) -> f64 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+/// Real roots of `x³ + a x² + b x + c`.
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    if r * r < q * q * q {
+        let t = (r / q.powf(1.5)).clamp(-1.0, 1.0).acos();
+        let s = -2.0 * q.sqrt();
+        (0..3)
+            .map(|k| s * ((t + 2.0 * PI * k as f64) / 3.0).cos() - a / 3.0)
+            .collect()
+    } else {
+        let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let big_b = if big_a != 0.0 { q / big_a } else { 0.0 };
+        vec![big_a + big_b - a / 3.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ellipse_arc(cx: f64, cy: f64, ax: f64, bx: f64, theta: f64, start: f64, end: f64) -> Ring {
+        Ring { width: 2.0, wmin: 1.0, wmax: 3.0, ..Ring::ellipse(cx, cy, ax, bx, theta).arc(start, end) }
+    }
+
+    #[test]
+    fn test_nms_keeps_best_of_duplicates() {
+        let a = ellipse_arc(50.0, 50.0, 20.0, 10.0, 0.3, 0.0, 5.0);
+        let b = ellipse_arc(50.5, 50.0, 20.0, 10.0, 0.3, 0.0, 6.0);
+        let c = ellipse_arc(150.0, 50.0, 20.0, 10.0, 0.3, 0.0, 5.0);
+        let rings = [a, b, c];
+
+        let kept = nms(&rings, Score::ArcCoverage, 0.5).unwrap();
+        assert_eq!(kept, vec![1, 2]);
+
+        let kept = nms(&rings, Score::Provided(&[3.0, 1.0, 2.0]), 0.5).unwrap();
+        assert_eq!(kept, vec![0, 2]);
+
+        assert!(nms(&rings, Score::Provided(&[1.0]), 0.5).is_err());
+    }
+
+    #[test]
+    fn test_fit_ellipse_recovers_parameters() {
+        let truth = ellipse_arc(40.0, -12.0, 30.0, 12.0, 0.6, 0.0, 2.0 * PI);
+        let points: Vec<Pt> = (0..50).map(|i| truth.point_at(i as f64 * 0.1)).collect();
+        let Ring { cx, cy, ax, bx, theta, .. } = fit_ellipse(&points).unwrap();
+
+        assert!((cx - 40.0).abs() < 1e-6 && (cy + 12.0).abs() < 1e-6);
+        // 轴的顺序可能交换，角度相差 π/2
+        let (major, minor) = (ax.max(bx), ax.min(bx));
+        assert!((major - 30.0).abs() < 1e-6 && (minor - 12.0).abs() < 1e-6);
+        let major_theta = if ax >= bx { theta } else { theta + PI / 2.0 };
+        assert!((major_theta - 0.6).rem_euclid(PI).min((0.6 - major_theta).rem_euclid(PI)) < 1e-6);
+    }
+
+    #[test]
+    fn test_merge_arcs_of_same_ellipse() {
+        let first = ellipse_arc(100.0, 80.0, 40.0, 25.0, 0.2, 0.0, 2.2);
+        let second = ellipse_arc(100.0, 80.0, 40.0, 25.0, 0.2, 2.0, 4.4);
+        let third = ellipse_arc(100.0, 80.0, 40.0, 25.0, 0.2, 4.2, 6.3);
+        let other = ellipse_arc(300.0, 80.0, 20.0, 20.0, 0.0, 0.0, 3.0);
+        let rings = [first, other, second, third];
+
+        let params = MergeParams::default();
+        assert_eq!(group_arcs(&rings, &params), vec![vec![0, 2, 3], vec![1]]);
+
+        let merged = merge_arcs(&rings, &params);
+        assert_eq!(merged.len(), 2);
+        let ring = merged[0];
+        assert_eq!(ring.full, 1);
+        assert!((ring.cx - 100.0).abs() < 1e-3 && (ring.cy - 80.0).abs() < 1e-3);
+        let truth = Ring::ellipse(100.0, 80.0, 40.0, 25.0, 0.2);
+        assert!(ring.iou(&truth) > 0.999);
+        assert_eq!(merged[1].cx, other.cx);
+    }
+
+    #[test]
+    fn test_merge_partial_arcs_stays_partial() {
+        let first = ellipse_arc(0.0, 0.0, 30.0, 30.0, 0.0, 0.0, 1.0);
+        let second = ellipse_arc(0.0, 0.0, 30.0, 30.0, 0.0, 0.9, 2.0);
+        let merged = merge_arcs(&[first, second], &MergeParams::default());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].full, 0);
+        assert!((merged[0].angular_coverage() - 2.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,484 @@
+/**
+ * File: /src/roi.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Saturday, 17th October 2026 11:58:21 pm
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::elsdc::{Detection, Detector};
+use crate::label_map::LabelMap;
+use crate::primitives::Image;
+use crate::ElsdcError;
+
+/// Relaxation passes applied after the masked-out pixels have been filled.
+const SMOOTHING_PASSES: usize = 16;
+
+/// A primitive is kept when at least this fraction of its supporting pixels
+/// lies inside the mask.
+const MIN_SUPPORT_INSIDE: f64 = 0.5;
+
+/// Axis-aligned rectangle of pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Roi {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+
+    /// Part of the rectangle inside a `width x height` image, `None` if empty.
+    pub fn clip(&self, width: u32, height: u32) -> Option<Roi> {
+        let x_end = self.x.saturating_add(self.width).min(width);
+        let y_end = self.y.saturating_add(self.height).min(height);
+        (self.x < x_end && self.y < y_end).then(|| Roi::new(self.x, self.y, x_end - self.x, y_end - self.y))
+    }
+}
+
+/// Parses `x,y,width,height`.
+impl FromStr for Roi {
+    type Err = ElsdcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ElsdcError::ConfigError(format!("Invalid ROI '{}': {}", s, e)))?;
+        match values[..] {
+            [x, y, width, height] => Ok(Roi::new(x, y, width, height)),
+            _ => Err(ElsdcError::ConfigError(format!("Invalid ROI '{}': expected x,y,width,height", s))),
+        }
+    }
+}
+
+/// Binary mask of the pixels to search; `true` pixels are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Mask {
+    width: u32,
+    height: u32,
+    data: Vec<bool>,
+}
+
+impl Mask {
+    /// Wraps row-major mask values; `data` must hold `width * height` values.
+    pub fn new(width: u32, height: u32, data: Vec<bool>) -> Result<Self, ElsdcError> {
+        if data.len() != width as usize * height as usize {
+            return Err(ElsdcError::ImageConversionError(format!(
+                "Mask has {} entries, expected {}x{}",
+                data.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self { width, height, data })
+    }
+
+    pub fn from_fn<F: FnMut(u32, u32) -> bool>(width: u32, height: u32, mut f: F) -> Self {
+        let data = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| f(x, y)).collect();
+        Self { width, height, data }
+    }
+
+    /// Mask of a `width x height` image that keeps only `roi`.
+    pub fn from_roi(width: u32, height: u32, roi: Roi) -> Self {
+        Self::from_fn(width, height, |x, y| roi.contains(x, y))
+    }
+
+    /// Keeps the pixels of `image` brighter than `threshold`.
+    pub fn from_image<I: Image + ?Sized>(image: &I, threshold: f64) -> Result<Self, ElsdcError> {
+        let data = pixels(image)?.iter().map(|&v| v > threshold).collect();
+        Self::new(image.width(), image.height(), data)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Mask values in row-major order.
+    pub fn data(&self) -> &[bool] {
+        &self.data
+    }
+
+    /// Whether `(x, y)` is kept; `false` outside the mask.
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height && self.data[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Number of kept pixels.
+    pub fn count(&self) -> usize {
+        self.data.iter().filter(|&&v| v).count()
+    }
+
+    /// Smallest rectangle holding every kept pixel, `None` for an empty mask.
+    pub fn bounding_box(&self) -> Option<Roi> {
+        let width = self.width.max(1) as usize;
+        let mut kept = self.data.iter().enumerate().filter(|(_, &v)| v).map(|(i, _)| (i % width, i / width));
+        let (x, y) = kept.next()?;
+        let (x0, y0, x1, y1) = kept.fold((x, y, x, y), |(x0, y0, x1, y1), (x, y)| {
+            (x0.min(x), y0.min(y), x1.max(x), y1.max(y))
+        });
+        Some(Roi::new(x0 as u32, y0 as u32, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32))
+    }
+}
+
+impl Detector {
+    /// Detects inside `roi` only; coordinates and labels refer to the full image.
+    ///
+    /// Only the rectangle is handed to ELSDc, so a small ROI is also faster.
+    pub fn detect_roi<I: Image + ?Sized>(&self, image: &I, roi: Roi) -> Result<Detection, ElsdcError> {
+        let (width, height) = (image.width(), image.height());
+        let clipped = roi.clip(width, height).ok_or_else(|| {
+            ElsdcError::DetectionError(format!("ROI {:?} lies outside the {}x{} image", roi, width, height))
+        })?;
+        let data = crop(&pixels(image)?, width, clipped);
+        let detection = self.detect_slice(&data, clipped.width, clipped.height)?;
+        to_full_image(detection, clipped, width, height)
+    }
+
+    /// Detects on the pixels kept by `mask`, which must match the image size.
+    ///
+    /// Masked-out pixels are filled from their surroundings so they carry
+    /// (almost) no gradient, and primitives supported mostly by them are
+    /// dropped. Detection runs on the bounding box of the mask only.
+    pub fn detect_masked<I: Image + ?Sized>(&self, image: &I, mask: &Mask) -> Result<Detection, ElsdcError> {
+        let (width, height) = (image.width(), image.height());
+        if (mask.width, mask.height) != (width, height) {
+            return Err(ElsdcError::ImageConversionError(format!(
+                "Mask is {}x{} but the image is {}x{}",
+                mask.width, mask.height, width, height
+            )));
+        }
+        let Some(roi) = mask.bounding_box() else {
+            let labels = LabelMap::new(width, height, vec![LabelMap::BACKGROUND; mask.data.len()])?;
+            return Ok(Detection { labels, ..Detection::default() });
+        };
+
+        let mut data = crop(&pixels(image)?, width, roi);
+        let inside = crop(&mask.data, width, roi);
+        fill_outside(&mut data, &inside, roi.width as usize);
+
+        let mut detection = self.detect_slice(&data, roi.width, roi.height)?;
+        restrict_to_mask(&mut detection, &inside);
+        to_full_image(detection, roi, width, height)
+    }
+}
+
+/// Borrows the pixels behind [`Image::as_ptr`], or copies them with
+/// [`Image::copy_pixels`] when the image cannot expose them in place.
+pub(crate) fn pixels<I: Image + ?Sized>(image: &I) -> Result<Cow<'_, [f64]>, ElsdcError> {
+    let len = image.width() as usize * image.height() as usize;
+    let ptr = image.as_ptr();
+    if ptr.is_null() {
+        let data = image
+            .copy_pixels()
+            .map_err(|e| ElsdcError::DetectionError(format!("Failed to read the image pixels: {}", e)))?;
+        if data.len() != len {
+            return Err(ElsdcError::DetectionError(format!(
+                "Expected {} pixels for a {}x{} image, got {}",
+                len,
+                image.width(),
+                image.height(),
+                data.len()
+            )));
+        }
+        return Ok(Cow::Owned(data));
+    }
+    // SAFETY: a non-null `as_ptr` points to `width * height` row-major pixels
+    // that live as long as the borrow of `image` (the `Image` contract).
+    Ok(Cow::Borrowed(unsafe { std::slice::from_raw_parts(ptr, len) }))
+}
+
+/// Copies the `roi` rows out of a row-major buffer `width` pixels wide.
+pub(crate) fn crop<T: Copy>(data: &[T], width: u32, roi: Roi) -> Vec<T> {
+    (roi.y..roi.y + roi.height)
+        .flat_map(|y| {
+            let start = y as usize * width as usize + roi.x as usize;
+            data[start..start + roi.width as usize].iter().copied()
+        })
+        .collect()
+}
+
+/// Replaces the pixels outside the mask by values diffused inwards from the
+/// pixels inside it: each ring of unknown pixels takes the mean of its known
+/// neighbours, then a few relaxation passes smooth the result.
+fn fill_outside(data: &mut [f64], inside: &[bool], width: usize) {
+    let height = data.len() / width;
+    let neighbours = |i: usize| {
+        let (x, y) = (i % width, i / width);
+        [
+            (x > 0).then(|| i - 1),
+            (x + 1 < width).then(|| i + 1),
+            (y > 0).then(|| i - width),
+            (y + 1 < height).then(|| i + width),
+        ]
+        .into_iter()
+        .flatten()
+    };
+
+    let mut known = inside.to_vec();
+    let mut front: Vec<usize> = (0..data.len())
+        .filter(|&i| !known[i] && neighbours(i).any(|j| known[j]))
+        .collect();
+    while !front.is_empty() {
+        let values: Vec<f64> = front
+            .iter()
+            .map(|&i| {
+                let (sum, n) = neighbours(i).filter(|&j| known[j]).fold((0.0, 0), |(s, n), j| (s + data[j], n + 1));
+                sum / n as f64
+            })
+            .collect();
+        for (&i, v) in front.iter().zip(values) {
+            data[i] = v;
+            known[i] = true;
+        }
+        let mut next: Vec<usize> = front.iter().flat_map(|&i| neighbours(i)).filter(|&j| !known[j]).collect();
+        next.sort_unstable();
+        next.dedup();
+        front = next;
+    }
+
+    let outside: Vec<usize> = (0..data.len()).filter(|&i| !inside[i]).collect();
+    for _ in 0..SMOOTHING_PASSES {
+        let values: Vec<f64> = outside
+            .iter()
+            .map(|&i| {
+                let (sum, n) = neighbours(i).fold((0.0, 0), |(s, n), j| (s + data[j], n + 1));
+                sum / n as f64
+            })
+            .collect();
+        for (&i, v) in outside.iter().zip(values) {
+            data[i] = v;
+        }
+    }
+}
+
+/// Drops the primitives supported mostly outside the mask and clears the
+/// labels of every pixel that is outside it or belongs to a dropped primitive.
+fn restrict_to_mask(detection: &mut Detection, inside: &[bool]) {
+    let mut support: HashMap<i32, (usize, usize)> = HashMap::new();
+    for (&label, &kept) in detection.labels.data.iter().zip(inside) {
+        if label != LabelMap::BACKGROUND {
+            let (n_inside, total) = support.entry(label).or_default();
+            *n_inside += kept as usize;
+            *total += 1;
+        }
+    }
+    let keep = |label: i32| {
+        support
+            .get(&label)
+            .is_some_and(|&(n_inside, total)| n_inside as f64 >= MIN_SUPPORT_INSIDE * total as f64)
+    };
+
+    let (rings, ring_labels) = detection
+        .rings
+        .iter()
+        .zip(&detection.ring_labels)
+        .filter(|(_, &label)| keep(label))
+        .map(|(ring, &label)| (*ring, label))
+        .unzip();
+    let (polygons, polygon_labels) = detection
+        .polygons
+        .iter()
+        .zip(&detection.polygon_labels)
+        .filter(|(_, &label)| keep(label))
+        .map(|(polygon, &label)| (polygon.clone(), label))
+        .unzip();
+    detection.rings = rings;
+    detection.ring_labels = ring_labels;
+    detection.polygons = polygons;
+    detection.polygon_labels = polygon_labels;
+
+    for (label, &kept) in detection.labels.data.iter_mut().zip(inside) {
+        if !kept || !keep(*label) {
+            *label = LabelMap::BACKGROUND;
+        }
+    }
+}
+
+/// Moves a detection made on the `roi` crop back into a `width x height` image.
+fn to_full_image(detection: Detection, roi: Roi, width: u32, height: u32) -> Result<Detection, ElsdcError> {
+    let (dx, dy) = (roi.x as f64, roi.y as f64);
+    let mut labels = vec![LabelMap::BACKGROUND; width as usize * height as usize];
+    for (row, chunk) in detection.labels.data.chunks(roi.width as usize).enumerate() {
+        let start = (roi.y as usize + row) * width as usize + roi.x as usize;
+        labels[start..start + chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(Detection {
+        rings: detection.rings.iter().map(|ring| ring.translated(dx, dy)).collect(),
+        ring_labels: detection.ring_labels,
+        polygons: detection.polygons.iter().map(|polygon| polygon.translated(dx, dy)).collect(),
+        polygon_labels: detection.polygon_labels,
+        labels: LabelMap::new(width, height, labels)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gray_image::GrayImage;
+    use crate::polygon::{PointD, Polygon};
+    use crate::ring::Ring;
+
+    #[test]
+    fn test_roi_parse_and_clip() {
+        let roi: Roi = "10, 20,30,40".parse().unwrap();
+        assert_eq!(roi, Roi::new(10, 20, 30, 40));
+        assert!("1,2,3".parse::<Roi>().is_err());
+        assert!("a,2,3,4".parse::<Roi>().is_err());
+
+        assert_eq!(roi.clip(25, 100), Some(Roi::new(10, 20, 15, 40)));
+        assert_eq!(roi.clip(10, 100), None);
+        assert!(roi.contains(39, 59) && !roi.contains(40, 59));
+    }
+
+    #[test]
+    fn test_mask_bounding_box() {
+        let mask = Mask::from_roi(8, 6, Roi::new(2, 1, 3, 4));
+        assert_eq!(mask.count(), 12);
+        assert_eq!(mask.bounding_box(), Some(Roi::new(2, 1, 3, 4)));
+        assert_eq!(Mask::from_fn(4, 4, |_, _| false).bounding_box(), None);
+
+        let gray = GrayImage::from_vec(2, 1, vec![0.0, 255.0]).unwrap();
+        assert_eq!(Mask::from_image(&gray, 127.5).unwrap().data(), &[false, true]);
+    }
+
+    #[test]
+    fn test_fill_outside_is_smooth() {
+        // 左半边保留（0 与 200 的竖条），右半边被遮住
+        let width = 8;
+        let inside: Vec<bool> = (0..64).map(|i| i % width < 4).collect();
+        let mut data: Vec<f64> = (0..64).map(|i| if i % width == 3 { 200.0 } else { 0.0 }).collect();
+        data.iter_mut().zip(&inside).filter(|(_, &k)| !k).for_each(|(v, _)| *v = 255.0);
+
+        fill_outside(&mut data, &inside, width);
+        for y in 0..8 {
+            let row = &data[y * width..(y + 1) * width];
+            assert_eq!(row[3], 200.0);
+            for x in 4..width {
+                assert!(row[x] <= 200.0 && row[x] > 0.0, "{:?}", row);
+                assert!(row[x] <= row[x - 1] + 1e-9, "{:?}", row);
+            }
+        }
+    }
+
+    #[test]
+    fn test_restrict_and_map_back() {
+        // 4x3 的裁剪区域，右两列在掩膜外
+        let inside: Vec<bool> = (0..12).map(|i| i % 4 < 2).collect();
+        let mut detection = Detection {
+            rings: vec![Ring::circle(1.0, 1.0, 10.0), Ring::circle(3.0, 1.0, 10.0)],
+            ring_labels: vec![1, 2],
+            polygons: vec![Polygon { points: vec![PointD { x: 0.0, y: 0.0 }, PointD { x: 1.0, y: 2.0 }] }],
+            polygon_labels: vec![3],
+            labels: LabelMap::new(4, 3, vec![1, 1, 2, 2, 1, 3, 2, 2, 0, 3, 1, 2]).unwrap(),
+        };
+        restrict_to_mask(&mut detection, &inside);
+        assert_eq!(detection.ring_labels, vec![1]);
+        assert_eq!(detection.polygon_labels, vec![3]);
+        assert_eq!(detection.labels.data, vec![1, 1, 0, 0, 1, 3, 0, 0, 0, 3, 0, 0]);
+
+        let full = to_full_image(detection, Roi::new(5, 2, 4, 3), 10, 6).unwrap();
+        assert_eq!((full.rings[0].cx, full.rings[0].cy), (6.0, 3.0));
+        assert_eq!(full.rings[0].x1, 16.0);
+        assert_eq!(full.polygons[0].points[1], PointD { x: 6.0, y: 4.0 });
+        assert_eq!(full.labels.get(5, 2), Some(1));
+        assert_eq!(full.labels.get(6, 3), Some(3));
+        assert_eq!(full.labels.count(1), 3);
+    }
+
+    #[test]
+    fn test_detect_roi_and_mask() {
+        let (width, height) = (120, 80);
+        let data = (0..width * height)
+            .map(|i| {
+                let (dx, dy) = (((i % width) as f64 - 80.0) / 20.0, ((i / width) as f64 - 40.0) / 15.0);
+                if dx * dx + dy * dy < 1.0 { 255.0 } else { 0.0 }
+            })
+            .collect();
+        let image = GrayImage::from_vec(width, height, data).unwrap();
+        let roi = Roi::new(50, 10, 60, 60);
+        let detector = Detector::new();
+
+        let detection = detector.detect_roi(&image, roi).unwrap();
+        assert_eq!((detection.labels.width, detection.labels.height), (width, height));
+        for ring in &detection.rings {
+            assert!(roi.contains(ring.cx as u32, ring.cy as u32), "{:?}", ring);
+        }
+        assert!(detector.detect_roi(&image, Roi::new(200, 0, 10, 10)).is_err());
+
+        let masked = detector.detect_masked(&image, &Mask::from_roi(width, height, roi)).unwrap();
+        for (i, &label) in masked.labels.data.iter().enumerate() {
+            if label != LabelMap::BACKGROUND {
+                assert!(roi.contains(i as u32 % width, i as u32 / width));
+            }
+        }
+        assert!(detector.detect_masked(&image, &Mask::from_fn(3, 3, |_, _| true)).is_err());
+        let empty = detector.detect_masked(&image, &Mask::from_fn(width, height, |_, _| false)).unwrap();
+        assert!(empty.rings.is_empty() && empty.labels.count(LabelMap::BACKGROUND) == 9600);
+    }
+
+    /// Column-major image that cannot hand out a row-major pointer.
+    struct Transposed(GrayImage);
+
+    unsafe impl Image for Transposed {
+        fn width(&self) -> u32 {
+            self.0.height()
+        }
+        fn height(&self) -> u32 {
+            self.0.width()
+        }
+        fn set_pixel(&mut self, x: u32, y: u32, value: f64) -> Result<(), Box<dyn std::error::Error>> {
+            self.0.set_pixel(y, x, value)
+        }
+        fn get_pixel(&self, x: u32, y: u32) -> Result<f64, Box<dyn std::error::Error>> {
+            self.0.get_pixel(y, x)
+        }
+        fn as_ptr(&self) -> *const f64 {
+            std::ptr::null()
+        }
+        fn as_mut_ptr(&mut self) -> *mut f64 {
+            std::ptr::null_mut()
+        }
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_pixels_copy_without_pointer() {
+        let gray = GrayImage::from_vec(3, 2, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        assert!(matches!(pixels(&gray).unwrap(), Cow::Borrowed(_)));
+
+        // 转置后按行读取，必须走逐像素复制
+        let transposed = Transposed(gray);
+        let data = pixels(&transposed).unwrap();
+        assert!(matches!(data, Cow::Owned(_)));
+        assert_eq!(&*data, &[0.0, 3.0, 1.0, 4.0, 2.0, 5.0]);
+        assert!(Detector::new().detect(&transposed).is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+/**
+ * File: /src/sink.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 5:58:03 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Stdout, Write};
+use std::path::Path;
+
+use crate::elsdc::Detection;
+use crate::polygon::Polygon;
+use crate::ring::Ring;
+use crate::ElsdcError;
+
+/// One detected primitive handed to a [`ResultSink`].
+#[derive(Clone, Copy, Debug)]
+pub enum SinkRecord<'a> {
+    Ring { index: usize, ring: &'a Ring },
+    Polygon { index: usize, polygon: &'a Polygon },
+}
+
+/// Destination of detection results, kept apart from drawing.
+///
+/// Nothing in the library writes results on its own; callers pick a sink
+/// and feed it explicitly.
+pub trait ResultSink {
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError>;
+
+    /// Called once all results have been recorded.
+    fn finish(&mut self) -> Result<(), ElsdcError> {
+        Ok(())
+    }
+
+    /// Records every ring, then every polygon, and finishes.
+    fn record_all(&mut self, rings: &[Ring], polygons: &[Polygon]) -> Result<(), ElsdcError> {
+        for (index, ring) in rings.iter().enumerate() {
+            self.record(SinkRecord::Ring { index, ring })?;
+        }
+        for (index, polygon) in polygons.iter().enumerate() {
+            self.record(SinkRecord::Polygon { index, polygon })?;
+        }
+        self.finish()
+    }
+
+    /// Records the rings and polygons of `detection`, and finishes.
+    fn record_detection(&mut self, detection: &Detection) -> Result<(), ElsdcError> {
+        self.record_all(&detection.rings, &detection.polygons)
+    }
+}
+
+impl<S: ResultSink + ?Sized> ResultSink for &mut S {
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError> {
+        (**self).record(record)
+    }
+
+    fn finish(&mut self) -> Result<(), ElsdcError> {
+        (**self).finish()
+    }
+}
+
+impl<S: ResultSink + ?Sized> ResultSink for Box<S> {
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError> {
+        (**self).record(record)
+    }
+
+    fn finish(&mut self) -> Result<(), ElsdcError> {
+        (**self).finish()
+    }
+}
+
+/// Writes one line of text per primitive.
+pub struct WriterSink<W: Write> {
+    writer: W,
+}
+
+/// A [`WriterSink`] writing to a file.
+pub type FileSink = WriterSink<BufWriter<File>>;
+/// A [`WriterSink`] writing to standard output.
+pub type StdoutSink = WriterSink<Stdout>;
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl FileSink {
+    /// Truncates or creates `path`, creating parent directories as needed.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        Self::open(path.as_ref(), false)
+    }
+
+    /// Appends to `path`, creating it (and its parent directories) if needed.
+    pub fn append<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        Self::open(path.as_ref(), true)
+    }
+
+    fn open(path: &Path, append: bool) -> Result<Self, ElsdcError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().write(true).create(true).append(append).truncate(!append).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl StdoutSink {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> ResultSink for WriterSink<W> {
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError> {
+        match record {
+            SinkRecord::Ring { index, ring } => writeln!(
+                self.writer,
+                "Ring {}: center=({}, {}), axes=({}, {}), angle={}, startAngle={}, endAngle={}, full={}",
+                index, ring.cx, ring.cy, ring.ax, ring.bx, ring.theta, ring.ang_start, ring.ang_end, ring.full
+            )?,
+            SinkRecord::Polygon { index, polygon } => {
+                write!(self.writer, "Polygon {}: {} points:", index, polygon.points.len())?;
+                for p in &polygon.points {
+                    write!(self.writer, " ({}, {})", p.x, p.y)?;
+                }
+                writeln!(self.writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), ElsdcError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Collects the recorded primitives.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySink {
+    pub rings: Vec<Ring>,
+    pub polygons: Vec<Polygon>,
+}
+
+impl ResultSink for MemorySink {
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError> {
+        match record {
+            SinkRecord::Ring { ring, .. } => self.rings.push(*ring),
+            SinkRecord::Polygon { polygon, .. } => self.polygons.push(polygon.clone()),
+        }
+        Ok(())
+    }
+}
+
+/// Hands every record to a closure.
+pub struct CallbackSink<F> {
+    callback: F,
+}
+
+impl<F> CallbackSink<F>
+where
+    F: FnMut(SinkRecord<'_>) -> Result<(), ElsdcError>,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+impl<F> ResultSink for CallbackSink<F>
+where
+    F: FnMut(SinkRecord<'_>) -> Result<(), ElsdcError>,
+{
+    fn record(&mut self, record: SinkRecord<'_>) -> Result<(), ElsdcError> {
+        (self.callback)(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polygon::PointD;
+
+    fn ring(cx: f64) -> Ring {
+        Ring::circle(cx, 2.0, 3.0).arc(0.0, 1.0)
+    }
+
+    fn polygon() -> Polygon {
+        Polygon { points: vec![PointD { x: 1.0, y: 2.0 }, PointD { x: 3.5, y: 4.0 }] }
+    }
+
+    #[test]
+    fn test_writer_sink_lines() {
+        let mut sink = WriterSink::new(Vec::new());
+        sink.record_all(&[ring(1.0), ring(5.0)], &[polygon()]).unwrap();
+        let text = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("Ring 1: center=(5, 2), axes=(3, 3)"));
+        assert_eq!(lines[2], "Polygon 0: 2 points: (1, 2) (3.5, 4)");
+    }
+
+    #[test]
+    fn test_memory_and_callback_sinks() {
+        let mut memory = MemorySink::default();
+        memory.record_all(&[ring(1.0)], &[polygon()]).unwrap();
+        assert_eq!(memory.rings.len(), 1);
+        assert_eq!(memory.polygons[0], polygon());
+
+        let mut indices = Vec::new();
+        let mut sink = CallbackSink::new(|record| {
+            if let SinkRecord::Ring { index, .. } = record {
+                indices.push(index);
+            }
+            Ok(())
+        });
+        let boxed: &mut dyn ResultSink = &mut sink;
+        boxed.record_all(&[ring(1.0), ring(2.0), ring(3.0)], &[]).unwrap();
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_file_sink_appends() {
+        let path = std::env::temp_dir().join("elsdc_sink_test/results.txt");
+        fs::remove_file(&path).ok();
+        FileSink::create(&path).unwrap().record_all(&[ring(1.0)], &[]).unwrap();
+        FileSink::append(&path).unwrap().record_all(&[ring(2.0)], &[]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        FileSink::create(&path).unwrap().record_all(&[], &[]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        fs::remove_file(path).ok();
+    }
+}
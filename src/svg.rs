@@ -0,0 +1,369 @@
+/**
+ * File: /src/svg.rs
+ * Created Date: Sunday, October 18th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 4:27:50 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use std::fmt::{self, Write as _};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::elsdc::Detection;
+use crate::export::DetectionReport;
+use crate::polygon::Polygon;
+use crate::primitives::DetectedPrimitive;
+use crate::ring::Ring;
+use crate::ElsdcError;
+
+/// Image shown underneath the primitives of an [`SvgDocument`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SvgBackground {
+    /// Referenced by URL or path, resolved relative to the SVG file by the viewer.
+    Link(String),
+    /// Stored in the document as a base64 data URI.
+    Embedded { mime: String, data: Vec<u8> },
+}
+
+impl SvgBackground {
+    /// Embeds the file at `path`.
+    ///
+    /// Formats browsers cannot display (e.g. Netpbm) are re-encoded as PNG
+    /// with the `image` crate or OpenCV, and rejected without either.
+    pub fn embed<P: AsRef<Path>>(path: P) -> Result<Self, ElsdcError> {
+        let path = path.as_ref();
+        if let Some(mime) = browser_mime(path) {
+            return Ok(SvgBackground::Embedded { mime: mime.to_string(), data: fs::read(path)? });
+        }
+        #[cfg(feature = "image")]
+        {
+            Self::png(&crate::gray_image::GrayImage::open(path)?)
+        }
+        #[cfg(all(feature = "opencv", not(feature = "image")))]
+        {
+            use opencv::{core::Vector, imgcodecs, prelude::*};
+
+            let mat = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_UNCHANGED)?;
+            if mat.empty() {
+                return Err(ElsdcError::ImageReadError(format!("Failed to read {}", path.display())));
+            }
+            let mut buffer = Vector::<u8>::new();
+            imgcodecs::imencode(".png", &mat, &mut buffer, &Vector::new())?;
+            Ok(SvgBackground::Embedded { mime: "image/png".into(), data: buffer.to_vec() })
+        }
+        #[cfg(not(any(feature = "image", feature = "opencv")))]
+        Err(ElsdcError::ConfigError(format!(
+            "{} cannot be shown by a browser; link it or enable the `image` feature",
+            path.display()
+        )))
+    }
+
+    /// Embeds `image` encoded as PNG.
+    #[cfg(feature = "image")]
+    pub fn png(image: &crate::gray_image::GrayImage) -> Result<Self, ElsdcError> {
+        Ok(SvgBackground::Embedded { mime: "image/png".into(), data: crate::image_io::encode_png(image)? })
+    }
+
+    fn href(&self) -> String {
+        match self {
+            SvgBackground::Link(href) => href.clone(),
+            SvgBackground::Embedded { mime, data } => format!("data:{};base64,{}", mime, base64(data)),
+        }
+    }
+}
+
+/// Colours and layers of an [`SvgDocument`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgStyle {
+    /// Stroke colour of rings, any CSS colour.
+    pub ring_color: String,
+    /// Stroke colour of polygons, any CSS colour.
+    pub polygon_color: String,
+    /// Stroke width in image pixels.
+    pub stroke_width: f64,
+    /// Whether the polygon layer is written.
+    pub polygons: bool,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        Self {
+            ring_color: "#00c000".into(),
+            polygon_color: "#0080ff".into(),
+            stroke_width: 1.0,
+            polygons: true,
+        }
+    }
+}
+
+/// Detections as a scalable vector overlay.
+///
+/// Full rings become `<ellipse>` elements and arcs elliptical-arc `<path>`s,
+/// in image coordinates (pixel centres at integer positions). Every
+/// primitive has an id (`ring-3`, `polygon-0`), a class naming its kind and
+/// a `<title>` tooltip listing its parameters.
+#[derive(Clone, Debug, Default)]
+pub struct SvgDocument {
+    pub width: u32,
+    pub height: u32,
+    pub background: Option<SvgBackground>,
+    pub style: SvgStyle,
+    pub rings: Vec<Ring>,
+    pub polygons: Vec<Polygon>,
+}
+
+impl SvgDocument {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, ..Self::default() }
+    }
+
+    /// Document of the primitives of `detection`.
+    pub fn from_detection(detection: &Detection) -> Self {
+        Self {
+            rings: detection.rings.clone(),
+            polygons: detection.polygons.clone(),
+            ..Self::new(detection.labels.width, detection.labels.height)
+        }
+    }
+
+    /// Document of the primitives of a saved report.
+    pub fn from_report(report: &DetectionReport) -> Self {
+        Self {
+            rings: report.rings(),
+            polygons: report.polygons.iter().map(|record| record.polygon.clone()).collect(),
+            ..Self::new(report.image.width, report.image.height)
+        }
+    }
+
+    pub fn with_background(mut self, background: SvgBackground) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn with_style(mut self, style: SvgStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Writes the document to `writer`.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), ElsdcError> {
+        write!(writer, "{}", self)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the document to `path`, creating parent directories as needed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ElsdcError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl fmt::Display for SvgDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (w, h) = (self.width, self.height);
+        writeln!(
+            f,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"-0.5 -0.5 {w} {h}\">"
+        )?;
+        if let Some(background) = &self.background {
+            writeln!(
+                f,
+                "  <image id=\"background\" x=\"-0.5\" y=\"-0.5\" width=\"{w}\" height=\"{h}\" \
+                 style=\"image-rendering:pixelated\" href=\"{}\"/>",
+                escape(&background.href())
+            )?;
+        }
+
+        writeln!(
+            f,
+            "  <g id=\"rings\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\">",
+            escape(&self.style.ring_color),
+            num(self.style.stroke_width)
+        )?;
+        for (i, ring) in self.rings.iter().enumerate() {
+            write_ring(f, i, ring)?;
+        }
+        f.write_str("  </g>\n")?;
+
+        if self.style.polygons {
+            writeln!(
+                f,
+                "  <g id=\"polygons\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\">",
+                escape(&self.style.polygon_color),
+                num(self.style.stroke_width)
+            )?;
+            for (i, polygon) in self.polygons.iter().enumerate() {
+                let points: Vec<String> =
+                    polygon.points.iter().map(|p| format!("{},{}", num(p.x), num(p.y))).collect();
+                writeln!(
+                    f,
+                    "    <polyline id=\"polygon-{i}\" class=\"polygon\" points=\"{}\"><title>polygon {i}: {} vertices, length {}</title></polyline>",
+                    points.join(" "),
+                    polygon.points.len(),
+                    num(polygon.length())
+                )?;
+            }
+            f.write_str("  </g>\n")?;
+        }
+        f.write_str("</svg>\n")
+    }
+}
+
+fn write_ring(f: &mut fmt::Formatter, i: usize, ring: &Ring) -> fmt::Result {
+    let kind = DetectedPrimitive::from(*ring).name();
+    let degrees = ring.theta.to_degrees();
+    let mut title = format!(
+        "ring {i}: centre ({}, {}), axes ({}, {}), theta {}°, width {}",
+        num(ring.cx),
+        num(ring.cy),
+        num(ring.ax),
+        num(ring.bx),
+        num(degrees),
+        num(ring.width)
+    );
+
+    if ring.full != 0 {
+        writeln!(
+            f,
+            "    <ellipse id=\"ring-{i}\" class=\"{}\" cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" transform=\"rotate({} {} {})\"><title>{}</title></ellipse>",
+            kind,
+            num(ring.cx),
+            num(ring.cy),
+            num(ring.ax),
+            num(ring.bx),
+            num(degrees),
+            num(ring.cx),
+            num(ring.cy),
+            title
+        )?;
+        return Ok(());
+    }
+
+    // 分成两段，每段不超过半圈，避免大弧标志和首尾重合的问题
+    let sweep = ring.angular_coverage();
+    let start = ring.point_at(ring.ang_start);
+    let middle = ring.point_at(ring.ang_start + sweep / 2.0);
+    let end = ring.point_at(ring.ang_start + sweep);
+    let arc = |(x, y): (f64, f64)| {
+        format!("A {} {} {} 0 1 {} {}", num(ring.ax), num(ring.bx), num(degrees), num(x), num(y))
+    };
+    write!(
+        title,
+        ", arc {}° to {}°, endpoints ({}, {}) ({}, {})",
+        num(ring.ang_start.to_degrees()),
+        num(ring.ang_end.to_degrees()),
+        num(ring.x1),
+        num(ring.y1),
+        num(ring.x2),
+        num(ring.y2)
+    )?;
+    writeln!(
+        f,
+        "    <path id=\"ring-{i}\" class=\"{}\" d=\"M {} {} {} {}\"><title>{}</title></path>",
+        kind,
+        num(start.0),
+        num(start.1),
+        arc(middle),
+        arc(end),
+        title
+    )
+}
+
+/// Numbers with at most three decimals and no trailing zeros.
+fn num(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".into() } else { text.into() }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// MIME type of the image formats every browser displays.
+fn browser_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polygon::PointD;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_document_elements() {
+        let mut doc = SvgDocument::new(40, 30).with_background(SvgBackground::Link("a&b.png".into()));
+        let ellipse = Ring::ellipse(20.0, 20.0, 10.0, 5.0, 0.0);
+        doc.rings = vec![ellipse, ellipse.arc(0.0, std::f64::consts::PI)];
+        doc.polygons = vec![Polygon { points: vec![PointD { x: 1.0, y: 2.0 }, PointD { x: 3.5, y: 4.0 }] }];
+        let svg = doc.to_string();
+
+        assert!(svg.contains("viewBox=\"-0.5 -0.5 40 30\""));
+        assert!(svg.contains("href=\"a&amp;b.png\""));
+        assert!(svg.contains("<ellipse id=\"ring-0\" class=\"ellipse\" cx=\"20\" cy=\"20\" rx=\"10\" ry=\"5\""));
+        // 半个椭圆：从 (30, 20) 经 (20, 25) 到 (10, 20)
+        assert!(svg.contains("<path id=\"ring-1\" class=\"elliptical-arc\" d=\"M 30 20 A 10 5 0 0 1 20 25 A 10 5 0 0 1 10 20\""));
+        assert!(svg.contains("<polyline id=\"polygon-0\" class=\"polygon\" points=\"1,2 3.5,4\">"));
+        assert!(svg.contains("<title>ring 1: centre (20, 20), axes (10, 5), theta 0°"));
+
+        doc.style.polygons = false;
+        assert!(!doc.to_string().contains("polygon-0"));
+    }
+
+    #[test]
+    fn test_embed_background() {
+        let path = std::env::temp_dir().join("elsdc_svg_embed_test.png");
+        fs::write(&path, b"fo").unwrap();
+        let background = SvgBackground::embed(&path).unwrap();
+        assert_eq!(background.href(), "data:image/png;base64,Zm8=");
+        fs::remove_file(path).ok();
+    }
+}
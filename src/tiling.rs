@@ -0,0 +1,312 @@
+/**
+ * File: /src/tiling.rs
+ * Created Date: Saturday, October 17th 2026
+ * Author: Zihan
+ * -----
+ * Last Modified: Sunday, 18th October 2026 12:47:09 am
+ * Modified By: the developer formerly known as Zihan at <wzh4464@gmail.com>
+ * -----
+ * HISTORY:
+ * Date      		By   	Comments
+ * ----------		------	---------------------------------------------------------
+**/
+
+use crate::elsdc::Detector;
+use crate::pgm::NetpbmRows;
+use crate::polygon::Polygon;
+use crate::primitives::{DetectedPrimitive, Image};
+use crate::ring::nms::{self, MergeParams, Score};
+use crate::ring::{BoundingBox, Ring};
+use crate::roi::{self, Roi};
+use crate::ElsdcError;
+
+/// Source of pixels that can be read one rectangle at a time.
+///
+/// Every [`Image`] is a tile source; [`NetpbmRows`] streams binary Netpbm
+/// files from disk so the whole image never has to be in memory.
+pub trait TileSource {
+    /// Width and height of the whole image.
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Row-major grayscale pixels of `roi`, which lies inside the image.
+    fn read_tile(&mut self, roi: Roi) -> Result<Vec<f64>, ElsdcError>;
+}
+
+impl<I: Image + ?Sized> TileSource for I {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    fn read_tile(&mut self, roi: Roi) -> Result<Vec<f64>, ElsdcError> {
+        Ok(roi::crop(&roi::pixels(self)?, self.width(), roi))
+    }
+}
+
+impl TileSource for NetpbmRows {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width() as u32, self.height() as u32)
+    }
+
+    fn read_tile(&mut self, roi: Roi) -> Result<Vec<f64>, ElsdcError> {
+        let tile = self.read_gray(roi.x as usize, roi.y as usize, roi.width as usize, roi.height as usize)?;
+        Ok(tile.data)
+    }
+}
+
+/// Layout of tiled detection and how results are stitched across seams.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileConfig {
+    /// Side of a square tile in pixels.
+    pub tile_size: u32,
+    /// Pixels shared by neighbouring tiles; must be smaller than `tile_size`.
+    pub overlap: u32,
+    /// Grouping of arcs of the same ellipse found in different tiles.
+    pub merge: MergeParams,
+    /// Rings near a seam overlapping a better one by more than this IoU are dropped.
+    pub iou_threshold: f64,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        Self { tile_size: 2048, overlap: 128, merge: MergeParams::default(), iou_threshold: 0.5 }
+    }
+}
+
+impl TileConfig {
+    pub fn new(tile_size: u32, overlap: u32) -> Self {
+        Self { tile_size, overlap, ..Self::default() }
+    }
+
+    fn validate(&self) -> Result<(), ElsdcError> {
+        if self.tile_size == 0 || self.overlap >= self.tile_size {
+            return Err(ElsdcError::ConfigError(format!(
+                "Tile overlap ({}) must be smaller than the tile size ({})",
+                self.overlap, self.tile_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Start of every tile along an axis of length `len`.
+    fn starts(&self, len: u32) -> Vec<u32> {
+        let step = self.tile_size - self.overlap;
+        let mut starts = vec![0];
+        while let Some(&last) = starts.last().filter(|&&s| s + self.tile_size < len) {
+            starts.push(last + step);
+        }
+        starts
+    }
+
+    /// Tiles covering a `width x height` image, row by row.
+    pub fn tiles(&self, width: u32, height: u32) -> Vec<Roi> {
+        let xs = self.starts(width);
+        self.starts(height)
+            .into_iter()
+            .flat_map(|y| xs.iter().map(move |&x| Roi::new(x, y, self.tile_size, self.tile_size)))
+            .filter_map(|tile| tile.clip(width, height))
+            .collect()
+    }
+}
+
+/// Result of [`Detector::detect_tiled`], in full-image coordinates.
+///
+/// Unlike [`crate::Detection`] there is no label map, which would be as large
+/// as the image itself.
+#[derive(Clone, Debug, Default)]
+pub struct TiledDetection {
+    pub width: u32,
+    pub height: u32,
+    pub rings: Vec<Ring>,
+    pub polygons: Vec<Polygon>,
+    /// Number of tiles processed.
+    pub tiles: usize,
+}
+
+impl TiledDetection {
+    /// Returns the detected rings followed by the polygons, classified by kind.
+    pub fn primitives(&self) -> Vec<DetectedPrimitive> {
+        let rings = self.rings.iter().map(|ring| DetectedPrimitive::from(*ring));
+        let polygons = self.polygons.iter().map(|polygon| DetectedPrimitive::from(polygon.clone()));
+        rings.chain(polygons).collect()
+    }
+}
+
+impl Detector {
+    /// Runs ELSDc tile by tile, so memory is bounded by the tile size.
+    ///
+    /// Rings whose ellipse reaches into an overlap band may have been cut by
+    /// a tile border or found twice; they are merged with
+    /// [`nms::merge_arcs_with_support`] and deduplicated with [`nms::nms`].
+    /// Polygons are kept by the tile whose core (the tile minus half the
+    /// overlap on each inner side) holds their mean point.
+    pub fn detect_tiled<S: TileSource + ?Sized>(
+        &self,
+        source: &mut S,
+        config: &TileConfig,
+    ) -> Result<TiledDetection, ElsdcError> {
+        config.validate()?;
+        let (width, height) = source.dimensions();
+        let tiles = config.tiles(width, height);
+        let seams = seam_bands(config, width, height);
+        let near_seam = |b: &BoundingBox| seams.iter().any(|seam| seam.intersects(b));
+
+        let mut result = TiledDetection { width, height, tiles: tiles.len(), ..TiledDetection::default() };
+        let (mut seam_rings, mut seam_supports) = (Vec::new(), Vec::new());
+        for tile in tiles {
+            let data = source.read_tile(tile)?;
+            let detection = self.detect_slice(&data, tile.width, tile.height)?;
+            let (dx, dy) = (tile.x as f64, tile.y as f64);
+
+            for (i, ring) in detection.rings.iter().enumerate() {
+                let ring = ring.translated(dx, dy);
+                if near_seam(&ring.bounding_box()) {
+                    let support = detection.ring_support(i).into_iter();
+                    seam_supports.push(support.map(|(x, y)| (x as f64 + dx, y as f64 + dy)).collect());
+                    seam_rings.push(ring);
+                } else {
+                    result.rings.push(ring);
+                }
+            }
+
+            let core = core_of(tile, config, width, height);
+            result.polygons.extend(
+                detection
+                    .polygons
+                    .iter()
+                    .filter(|polygon| mean_point(polygon).is_some_and(|p| in_core(&core, p)))
+                    .map(|polygon| polygon.translated(dx, dy)),
+            );
+        }
+
+        let merged = nms::merge_arcs_with_support(&seam_rings, &seam_supports, &config.merge)?;
+        let mut keep = nms::nms(&merged, Score::ArcLength, config.iou_threshold)?;
+        keep.sort_unstable();
+        result.rings.extend(keep.into_iter().map(|i| merged[i]));
+        Ok(result)
+    }
+}
+
+/// Overlap strips shared by neighbouring tiles, spanning the whole image.
+fn seam_bands(config: &TileConfig, width: u32, height: u32) -> Vec<BoundingBox> {
+    let overlap = config.overlap as f64;
+    let vertical = config.starts(width).into_iter().skip(1).map(|x| BoundingBox {
+        min_x: x as f64,
+        min_y: 0.0,
+        max_x: x as f64 + overlap,
+        max_y: height as f64,
+    });
+    let horizontal = config.starts(height).into_iter().skip(1).map(|y| BoundingBox {
+        min_x: 0.0,
+        min_y: y as f64,
+        max_x: width as f64,
+        max_y: y as f64 + overlap,
+    });
+    vertical.chain(horizontal).collect()
+}
+
+/// Part of `tile` (in tile coordinates) that owns its primitives: inner sides
+/// give up half of the overlap to the neighbouring tile.
+fn core_of(tile: Roi, config: &TileConfig, width: u32, height: u32) -> BoundingBox {
+    let half = config.overlap as f64 / 2.0;
+    let (w, h) = (tile.width as f64, tile.height as f64);
+    BoundingBox {
+        min_x: if tile.x == 0 { f64::NEG_INFINITY } else { half },
+        min_y: if tile.y == 0 { f64::NEG_INFINITY } else { half },
+        max_x: if tile.x + tile.width >= width { f64::INFINITY } else { w - half },
+        max_y: if tile.y + tile.height >= height { f64::INFINITY } else { h - half },
+    }
+}
+
+fn mean_point(polygon: &Polygon) -> Option<(f64, f64)> {
+    if polygon.points.is_empty() {
+        return None;
+    }
+    let n = polygon.points.len() as f64;
+    let (sx, sy) = polygon.points.iter().fold((0.0, 0.0), |(sx, sy), p| (sx + p.x, sy + p.y));
+    Some((sx / n, sy / n))
+}
+
+/// Half-open on the upper sides, so a point on a shared core edge has one owner.
+fn in_core(core: &BoundingBox, (x, y): (f64, f64)) -> bool {
+    x >= core.min_x && x < core.max_x && y >= core.min_y && y < core.max_y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gray_image::GrayImage;
+    use crate::polygon::PointD;
+
+    #[test]
+    fn test_tile_layout() {
+        let config = TileConfig::new(100, 20);
+        assert_eq!(config.starts(100), vec![0]);
+        assert_eq!(config.starts(101), vec![0, 80]);
+        assert_eq!(config.starts(250), vec![0, 80, 160]);
+
+        let tiles = config.tiles(250, 90);
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[2], Roi::new(160, 0, 90, 90));
+        assert!(TileConfig::new(100, 100).validate().is_err());
+        assert!(TileConfig::new(0, 0).validate().is_err());
+    }
+
+    #[test]
+    fn test_cores_partition_the_image() {
+        let config = TileConfig::new(100, 20);
+        let (width, height) = (250, 170);
+        let tiles = config.tiles(width, height);
+        for &(x, y) in &[(0.0, 0.0), (89.9, 10.0), (90.0, 10.0), (170.0, 90.0), (249.0, 169.0)] {
+            let owners = tiles
+                .iter()
+                .filter(|tile| {
+                    let core = core_of(**tile, &config, width, height);
+                    in_core(&core, (x - tile.x as f64, y - tile.y as f64))
+                })
+                .count();
+            assert_eq!(owners, 1, "({}, {})", x, y);
+        }
+        assert_eq!(seam_bands(&config, width, height).len(), 3);
+    }
+
+    #[test]
+    fn test_mean_point() {
+        let polygon = Polygon { points: vec![PointD { x: 0.0, y: 0.0 }, PointD { x: 4.0, y: 2.0 }] };
+        assert_eq!(mean_point(&polygon), Some((2.0, 1.0)));
+        assert_eq!(mean_point(&Polygon::default()), None);
+    }
+
+    #[test]
+    fn test_detect_tiled_matches_whole_image() {
+        // 两个椭圆：一个远离接缝，一个横跨 x = 100 附近的接缝
+        let (width, height) = (240u32, 120u32);
+        let ellipses = [(50.0, 60.0, 25.0, 18.0), (110.0, 60.0, 30.0, 22.0)];
+        let data = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as f64, (i / width) as f64);
+                let inside = ellipses
+                    .iter()
+                    .any(|&(cx, cy, a, b)| ((x - cx) / a).powi(2) + ((y - cy) / b).powi(2) < 1.0);
+                if inside { 255.0 } else { 0.0 }
+            })
+            .collect();
+        let mut image = GrayImage::from_vec(width, height, data).unwrap();
+        let detector = Detector::new();
+        let whole = detector.detect(&image).unwrap();
+
+        let tiled = detector.detect_tiled(&mut image, &TileConfig::new(120, 40)).unwrap();
+        assert_eq!(tiled.tiles, 3);
+        assert_eq!((tiled.width, tiled.height), (width, height));
+        for ring in &whole.rings {
+            assert!(
+                tiled.rings.iter().any(|r| r.iou(ring) > 0.8),
+                "ring {:?} lost in tiled detection",
+                ring
+            );
+        }
+        assert!(tiled.rings.len() <= whole.rings.len() + 1);
+        let primitives = tiled.primitives();
+        assert_eq!(primitives.len(), tiled.rings.len() + tiled.polygons.len());
+        assert!(primitives[tiled.rings.len()..].iter().all(|p| p.polygon().is_some()));
+    }
+}